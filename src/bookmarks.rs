@@ -0,0 +1,137 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// A bookmarked page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    /// The page number.
+    pub num: u16,
+
+    /// The page title, at the time the page was bookmarked.
+    pub title: String,
+}
+
+/// A list of bookmarked pages, persisted as JSON in a file.
+#[derive(Debug, Default)]
+pub struct Bookmarks {
+    path: Option<PathBuf>,
+    pages: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    /// Load bookmarks from the file at `path`. A missing file is treated as an
+    /// empty list; it is created when the bookmarks are first saved.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file cannot be read or deserialized.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let pages = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            path: Some(path),
+            pages,
+        })
+    }
+
+    /// Load bookmarks from the user's config directory, e.g.
+    /// `~/.config/textty/bookmarks.json` on Linux. If the platform has no
+    /// config directory, the bookmarks are kept in memory only.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file exists but cannot be read or deserialized.
+    pub fn open_default() -> Result<Self, Error> {
+        match dirs::config_dir() {
+            Some(dir) => Self::load(dir.join("textty").join("bookmarks.json")),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Save the bookmarks to their file, if any.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the bookmarks cannot be serialized or written to disk.
+    pub fn save(&self) -> Result<(), Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Write to a temporary file first, so that a crash while saving never
+        // leaves a truncated file.
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&self.pages)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Bookmark page `num`, or remove the bookmark if the page is already
+    /// bookmarked. Returns `true` if the page was bookmarked.
+    pub fn toggle(&mut self, num: u16, title: &str) -> bool {
+        if let Some(i) = self.pages.iter().position(|b| b.num == num) {
+            self.pages.remove(i);
+            false
+        } else {
+            self.pages.push(Bookmark {
+                num,
+                title: title.to_string(),
+            });
+            true
+        }
+    }
+
+    /// Remove the bookmark at `index`, if any.
+    pub fn remove(&mut self, index: usize) {
+        if index < self.pages.len() {
+            self.pages.remove(index);
+        }
+    }
+
+    /// Returns `true` if page `num` is bookmarked.
+    #[must_use]
+    pub fn contains(&self, num: u16) -> bool {
+        self.pages.iter().any(|b| b.num == num)
+    }
+
+    /// All bookmarks, in the order they were added.
+    #[must_use]
+    pub fn pages(&self) -> &[Bookmark] {
+        &self.pages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("textty").join("bookmarks.json");
+
+        let mut bookmarks = Bookmarks::load(&path).unwrap();
+        assert!(bookmarks.pages().is_empty());
+        assert!(bookmarks.toggle(377, "Resultat"));
+        assert!(bookmarks.toggle(401, "Väder"));
+        assert!(bookmarks.toggle(202, "Börsen"));
+        assert!(!bookmarks.toggle(401, "Väder"));
+        bookmarks.save().unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+
+        let bookmarks = Bookmarks::load(&path).unwrap();
+        let pages = bookmarks.pages().iter().map(|b| b.num).collect::<Vec<_>>();
+        assert_eq!(pages, [377, 202]);
+        assert!(bookmarks.contains(202));
+        assert!(!bookmarks.contains(401));
+    }
+}
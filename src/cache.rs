@@ -0,0 +1,156 @@
+//! A persistent cache of fetched pages, used when a page cannot be fetched.
+
+use crate::error::Error;
+use crate::texttv::{PageResponse, Validators};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// A page stored in the [`Cache`], along with the time it was stored.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedPage {
+    /// A UNIX timestamp representing the time the page was cached.
+    pub cached_unix: i64,
+
+    /// The cached page, as returned by the API.
+    pub page: PageResponse,
+
+    /// The HTTP validators of the response, to check whether the page has
+    /// changed.
+    #[serde(default)]
+    pub validators: Validators,
+}
+
+/// A persistent cache of pages, stored as one JSON file per page number.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Create a cache that stores pages in `dir`. The directory is created
+    /// when the first page is stored.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Open the cache `name` in the user's cache directory, e.g.
+    /// `~/.cache/textty/pages` on Linux. Returns `None` if the platform has no
+    /// cache directory.
+    #[must_use]
+    pub fn open_default(name: &str) -> Option<Self> {
+        dirs::cache_dir().map(|dir| Self::new(dir.join("textty").join(name)))
+    }
+
+    fn path(&self, number: u16) -> PathBuf {
+        self.dir.join(format!("{number}.json"))
+    }
+
+    fn mosaics_path(&self) -> PathBuf {
+        self.dir.join("mosaics.json")
+    }
+
+    /// Write `bytes` to `path` in the cache. The bytes are written to a
+    /// temporary file first, so that a reader never sees a partially written
+    /// file.
+    fn write(&self, path: PathBuf, bytes: &[u8]) -> Result<(), Error> {
+        fs::create_dir_all(&self.dir)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Store `page` in the cache, along with the `validators` of the response,
+    /// replacing any previously cached version.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the page cannot be serialized or written to disk.
+    pub fn store(&self, page: &PageResponse, validators: &Validators) -> Result<(), Error> {
+        let entry = CachedPage {
+            cached_unix: Utc::now().timestamp(),
+            page: page.clone(),
+            validators: validators.clone(),
+        };
+        self.write(self.path(page.num), &serde_json::to_vec(&entry)?)
+    }
+
+    /// Load page `number` from the cache. Returns `None` if the page has
+    /// not been cached.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the cached file cannot be read or deserialized.
+    pub fn load(&self, number: u16) -> Result<Option<CachedPage>, Error> {
+        let bytes = match fs::read(self.path(number)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Some(serde_json::from_slice(&bytes)?))
+    }
+
+    /// Store the glyphs of the mosaic GIFs decoded so far, by ID, replacing
+    /// the previously stored glyphs.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the glyphs cannot be serialized or written to disk.
+    pub fn store_mosaics(&self, mosaics: &HashMap<u64, char>) -> Result<(), Error> {
+        self.write(self.mosaics_path(), &serde_json::to_vec(mosaics)?)
+    }
+
+    /// Load the glyphs of the mosaic GIFs decoded in earlier runs, by ID.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the stored glyphs cannot be read or deserialized.
+    pub fn load_mosaics(&self) -> Result<HashMap<u64, char>, Error> {
+        match fs::read(self.mosaics_path()) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texttv::test_page;
+
+    #[test]
+    fn test_store_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path().join("pages"));
+
+        assert!(cache.load(377).unwrap().is_none());
+
+        let validators = Validators {
+            etag: Some("\"42\"".into()),
+            last_modified: None,
+        };
+        cache.store(&test_page(377), &validators).unwrap();
+        let cached = cache.load(377).unwrap().unwrap();
+        assert_eq!(cached.page.num, 377);
+        assert_eq!(cached.page.id, 377);
+        assert_eq!(cached.page.next_page, 378);
+        assert_eq!(cached.validators, validators);
+        assert!(cached.cached_unix > 0);
+    }
+
+    #[test]
+    fn test_store_and_load_mosaics() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path().join("pages"));
+
+        assert!(cache.load_mosaics().unwrap().is_empty());
+        let mosaics = HashMap::from([(42, '🬞'), (4_000_000_000, '█')]);
+        cache.store_mosaics(&mosaics).unwrap();
+        assert_eq!(cache.load_mosaics().unwrap(), mosaics);
+    }
+}
@@ -1,9 +1,198 @@
-use clap::Parser;
+pub use crate::mosaic::Glyphs;
+pub use crate::source::Backend;
+use crate::texttv::ClientBuilder;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[clap(author, version, about)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Display pages as plain text.
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub plain: bool,
+
+    /// Read pages from the local cache only, without using the network.
+    #[arg(short, long, global = true)]
+    pub offline: bool,
+
+    /// Use the number keys 1-8 to jump to bookmarks, instead of pages 100-800.
+    #[arg(short, long)]
+    pub bookmark_keys: bool,
+
+    /// Refresh the current page every SECONDS seconds; toggled with `a`.
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub refresh: Option<u64>,
+
+    /// Advance through multi-part pages every SECONDS seconds; toggled with `c`.
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub carousel: Option<u64>,
+
+    /// The pages fetched in the background after each page, to show them
+    /// without waiting.
+    #[arg(long, value_enum, default_value_t = Prefetch::Neighbours)]
+    pub prefetch: Prefetch,
+
+    /// The service to read pages from.
+    #[arg(long, global = true, value_enum, default_value_t = Backend::Texttv)]
+    pub backend: Backend,
+
+    /// The glyphs used to draw block graphics, for fonts that lack sextants.
+    #[arg(short, long, global = true, value_enum, default_value_t = Glyphs::Sextant)]
+    pub glyphs: Glyphs,
+
+    /// The base URL of the `texttv.nu` API.
+    #[arg(long, global = true, value_name = "URL", env = "TEXTTY_API_URL")]
+    pub api_url: Option<String>,
+
+    /// An HTTP proxy for all backends, e.g. `http://proxy.example.com:8080`.
+    #[arg(long, global = true, value_name = "URL", env = "TEXTTY_PROXY")]
+    pub proxy: Option<String>,
+
+    /// The time to wait for a connection, and for a response, in seconds.
+    #[arg(long, global = true, value_name = "SECONDS", env = "TEXTTY_TIMEOUT", value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: Option<u64>,
+}
+
+impl Cli {
+    /// The HTTP settings of the backends, from the arguments.
+    #[must_use]
+    pub fn client_settings(&self) -> ClientBuilder {
+        let mut settings = ClientBuilder::default();
+        if let Some(url) = &self.api_url {
+            settings = settings.base_url(url.as_str());
+        }
+        if let Some(proxy) = &self.proxy {
+            settings = settings.proxy(proxy.as_str());
+        }
+        if let Some(timeout) = self.timeout.map(Duration::from_secs) {
+            settings = settings.connect_timeout(timeout).read_timeout(timeout);
+        }
+        settings
+    }
+}
+
+/// The pages that the TUI fetches in the background.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Prefetch {
+    /// No pages.
+    Off,
+    /// The previous and next pages.
+    #[default]
+    Neighbours,
+    /// The previous and next pages, and the bookmarked pages.
+    Bookmarks,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print a page, or a range of pages, to stdout instead of starting the TUI.
+    ///
+    /// Pages are printed with colours if stdout is a terminal, and as plain
+    /// text otherwise.
+    Get {
+        /// A page number, e.g. `377`, or a range of pages, e.g. `100-105`.
+        pages: PageRange,
+
+        /// The output format.
+        #[arg(short, long, value_enum, default_value_t = Format::Auto)]
+        format: Format,
+    },
+
+    /// Fetch a page and print the rows that changed since it was cached.
+    ///
+    /// Old rows are prefixed by `-` and new rows by `+`, along with the row
+    /// number. The fetched page replaces the cached version.
+    Diff {
+        /// A page number, e.g. `377`.
+        page: u16,
+    },
+
+    /// Poll pages, and print a line to stdout whenever one of them changes.
+    ///
+    /// A line holds the update time, the page number and title, and the first
+    /// changed row. The pages are fetched once to start with, and only
+    /// changes after that are printed.
+    Watch {
+        /// The page numbers to watch, e.g. `377 401`.
+        #[arg(required = true)]
+        pages: Vec<u16>,
+
+        /// The time between polls, in seconds.
+        #[arg(short, long, value_name = "SECONDS", default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+
+        /// A shell command to run for each change, with the page as JSON (like
+        /// `get --format json`) on stdin, and its number in `TEXTTY_PAGE`.
+        #[arg(short, long, value_name = "CMD")]
+        exec: Option<String>,
+    },
+}
+
+/// The output format of the `get` subcommand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// ANSI colours if stdout is a terminal, plain text otherwise.
+    Auto,
+    /// Text with ANSI colour escape codes.
+    Ansi,
+    /// Text without colours.
+    Text,
+    /// One JSON object per page (JSON Lines), ignoring `--plain`.
+    Json,
+}
+
+/// An inclusive range of page numbers, parsed from e.g. `377` or `100-105`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageRange {
+    pub lo: u16,
+    pub hi: u16,
+}
+
+impl FromStr for PageRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |nr: &str| {
+            nr.trim()
+                .parse::<u16>()
+                .map_err(|err| format!("invalid page number '{nr}': {err}"))
+        };
+        let (lo, hi) = if let Some((lo, hi)) = s.split_once('-') {
+            (parse(lo)?, parse(hi)?)
+        } else {
+            let nr = parse(s)?;
+            (nr, nr)
+        };
+        if hi < lo {
+            return Err(format!(
+                "invalid page range '{s}': {lo} is greater than {hi}"
+            ));
+        }
+        Ok(Self { lo, hi })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_page_range() {
+        assert_eq!("377".parse(), Ok(PageRange { lo: 377, hi: 377 }));
+        assert_eq!("100-105".parse(), Ok(PageRange { lo: 100, hi: 105 }));
+        assert!("105-100".parse::<PageRange>().is_err());
+        assert!("abc".parse::<PageRange>().is_err());
+    }
+
+    #[test]
+    fn test_client_settings() {
+        let args = Cli::parse_from(["textty", "--proxy", "not a proxy"]);
+        for backend in [Backend::Texttv, Backend::Svt, Backend::Nrk] {
+            assert!(crate::source::for_backend(backend, &args.client_settings()).is_err());
+        }
+    }
 }
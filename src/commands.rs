@@ -0,0 +1,415 @@
+use crate::cache::Cache;
+use crate::cli::{Cli, Format, Glyphs, PageRange};
+use crate::error::Error;
+use crate::source::{self, PageSource};
+use crate::texttv::{PageResponse, PageUpdate, Validators};
+use crate::{diff, page, print, worker};
+use chrono::Local;
+use color_eyre::Result;
+use color_eyre::eyre::{bail, eyre};
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, IsTerminal, Write};
+use std::ops::RangeInclusive;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// Fetches the pages in `range`, from `source` or from the cache. A single
+/// page falls back to the cache if the request fails, like in the TUI.
+fn fetch_range(
+    source: &impl PageSource,
+    cache: Option<&Cache>,
+    offline: bool,
+    range: PageRange,
+) -> Result<Vec<PageResponse>, Error> {
+    if range.lo == range.hi {
+        let fetch = worker::fetch(source, cache, offline, range.lo)?;
+        return Ok(vec![fetch.response]);
+    }
+
+    if offline {
+        let mut pages = Vec::new();
+        if let Some(cache) = cache {
+            for page_nr in range.lo..=range.hi {
+                if let Some(cached) = cache.load(page_nr)? {
+                    worker::resolve_mosaics(source, Some(cache), &cached.page, offline);
+                    pages.push(cached.page);
+                }
+            }
+        }
+        if pages.is_empty() {
+            return Err(Error::NotCached(range.lo));
+        }
+        return Ok(pages);
+    }
+
+    let mut pages = source.get_page_range(range.lo, range.hi)?;
+    for page in &mut pages {
+        worker::complete(source, cache, page, None);
+    }
+    Ok(pages)
+}
+
+/// Opens the cache of the backend in `args`, and registers the mosaics
+/// decoded in earlier runs.
+fn open_cache(args: &Cli) -> Option<Cache> {
+    let cache = Cache::open_default(args.backend.cache_name());
+    worker::load_mosaics(cache.as_ref());
+    cache
+}
+
+/// Checks that the pages from `lo` to `hi` are in the page range of the
+/// backend in `args`.
+fn check_pages(args: &Cli, lo: u16, hi: u16) -> Result<()> {
+    let pages = args.backend.page_range();
+    if let Some(nr) = [lo, hi].into_iter().find(|nr| !pages.contains(nr)) {
+        bail!(
+            "page {nr} is out of range: pages are {}-{}",
+            pages.start(),
+            pages.end()
+        );
+    }
+    Ok(())
+}
+
+/// Writes all pages in the page set of `response` to `w`, separated by empty
+/// lines. With `plain`, the plain text content from the API is written.
+/// Otherwise, mosaics are drawn with `glyphs`.
+fn write_page(
+    w: &mut impl Write,
+    response: &PageResponse,
+    plain: bool,
+    colour: bool,
+    pages: &RangeInclusive<u16>,
+    glyphs: Glyphs,
+) -> Result<()> {
+    if plain {
+        for content in response.content_plain.iter().flatten() {
+            writeln!(w, "{}", content.trim_end())?;
+        }
+        return Ok(());
+    }
+
+    for (i, content) in response.content.iter().enumerate() {
+        if i > 0 {
+            writeln!(w)?;
+        }
+        let mut parsed = page::parse(content, pages)?;
+        parsed.set_glyphs(glyphs);
+        if colour {
+            print::write_ansi(w, &parsed)?;
+        } else {
+            print::write_plain(w, &parsed)?;
+        }
+    }
+    Ok(())
+}
+
+/// Parses the page set of `response`, with links to the page numbers in
+/// `pages` and mosaics drawn with `glyphs`.
+fn parse_page_set(
+    response: &PageResponse,
+    pages: &RangeInclusive<u16>,
+    glyphs: Glyphs,
+) -> Result<Vec<page::Page>, Error> {
+    response
+        .content
+        .iter()
+        .map(|content| {
+            let mut page = page::parse(content, pages)?;
+            page.set_glyphs(glyphs);
+            Ok(page)
+        })
+        .collect()
+}
+
+/// Runs the `diff` subcommand: fetches page `page_nr`, and prints the rows that
+/// changed since the cached version to stdout.
+///
+/// # Errors
+///
+/// Will return `Err` if the page is out of range or not cached, or if it
+/// cannot be fetched, parsed, or written.
+pub fn diff(args: &Cli, page_nr: u16) -> Result<()> {
+    check_pages(args, page_nr, page_nr)?;
+    let cache = open_cache(args);
+    let cached = cache
+        .as_ref()
+        .map(|cache| cache.load(page_nr))
+        .transpose()?
+        .flatten()
+        .ok_or(Error::NotCached(page_nr))?;
+    let source = source::for_backend(args.backend, &args.client_settings())?;
+    let fetch = if args.offline {
+        worker::fetch(&source, cache.as_ref(), true, page_nr)?
+    } else {
+        worker::refresh(&source, cache.as_ref(), &cached.page, &cached.validators)?
+    };
+
+    let stdout = io::stdout();
+    let colour = stdout.is_terminal();
+    let mut out = stdout.lock();
+    let since = cached.page.updated().map_or_else(
+        || "N/A".to_string(),
+        |dt| {
+            dt.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        },
+    );
+    let response = &fetch.response;
+    // An unchanged page is not parsed at all.
+    let mut diff = Vec::new();
+    let count = if fetch.changed {
+        let old = parse_page_set(&cached.page, &args.backend.page_range(), args.glyphs)?;
+        let new = parse_page_set(response, &args.backend.page_range(), args.glyphs)?;
+        print::write_diff(&mut diff, &old, &new, colour)?
+    } else {
+        0
+    };
+    if count == 0 {
+        writeln!(out, "{page_nr} {}: unchanged since {since}", response.title)?;
+    } else {
+        writeln!(
+            out,
+            "{page_nr} {}: {count} changed {} since {since}",
+            response.title,
+            if count == 1 { "row" } else { "rows" }
+        )?;
+        out.write_all(&diff)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// A compact line describing the change from `old` to `new`: the update time,
+/// the page number and title, and the first changed row, if any.
+fn change_line(
+    old: &PageResponse,
+    new: &PageResponse,
+    pages: &RangeInclusive<u16>,
+    glyphs: Glyphs,
+) -> String {
+    let updated = new.updated().map_or_else(
+        || "N/A".to_string(),
+        |dt| dt.with_timezone(&Local).format("%H:%M").to_string(),
+    );
+    let mut line = format!("{updated} {} {}", new.num, new.title);
+    // A page that cannot be parsed is still reported, without the row.
+    if let (Ok(old), Ok(new)) = (
+        parse_page_set(old, pages, glyphs),
+        parse_page_set(new, pages, glyphs),
+    ) {
+        let first = diff::changed_page_set(&old, &new)
+            .into_iter()
+            .enumerate()
+            .find_map(|(i, rows)| new[i].lines.get(*rows.first()?));
+        if let Some(row) = first {
+            line.push_str(": ");
+            line.push_str(diff::line_text(row).trim());
+        }
+    }
+    line
+}
+
+/// Runs `command` through the shell, with `response` as JSON on stdin.
+fn run_hook(
+    command: &str,
+    response: &PageResponse,
+    pages: &RangeInclusive<u16>,
+    glyphs: Glyphs,
+) -> Result<()> {
+    let mut json = Vec::new();
+    print::write_json(&mut json, response, pages, glyphs)?;
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut child = Command::new(shell)
+        .args([flag, command])
+        .env("TEXTTY_PAGE", response.num.to_string())
+        .stdin(Stdio::piped())
+        .spawn()?;
+    let written = child.stdin.take().map(|mut stdin| stdin.write_all(&json));
+    let status = child.wait()?;
+    match written {
+        // The command does not have to read the page.
+        Some(Err(err)) if err.kind() != ErrorKind::BrokenPipe => return Err(err.into()),
+        _ => {}
+    }
+    if !status.success() {
+        return Err(eyre!("`{command}` failed: {status}"));
+    }
+    Ok(())
+}
+
+/// Runs the `watch` subcommand: polls `pages` every `interval`, and prints a
+/// line to stdout whenever one of them changes. With `exec`, the command is
+/// run for each change. Errors while polling are printed to stderr, and the
+/// pages are polled again on the next interval.
+///
+/// # Errors
+///
+/// Will return `Err` if offline, if a page is outside the page range of the
+/// backend, or if writing to stdout fails.
+pub fn watch(args: &Cli, pages: &[u16], interval: Duration, exec: Option<&str>) -> Result<()> {
+    if args.offline {
+        bail!("cannot watch pages offline");
+    }
+    for &page_nr in pages {
+        check_pages(args, page_nr, page_nr)?;
+    }
+    let cache = open_cache(args);
+    let source = source::for_backend(args.backend, &args.client_settings())?;
+    let page_range = args.backend.page_range();
+    let mut known: HashMap<u16, (PageResponse, Validators)> = HashMap::new();
+    loop {
+        for &page_nr in pages {
+            let update = match known.get(&page_nr) {
+                Some((page, validators)) => source.get_page_if_changed(page, validators),
+                None => source
+                    .get_page_with_validators(page_nr)
+                    .map(|(page, validators)| PageUpdate::Changed { page, validators }),
+            };
+            let (mut page, validators) = match update {
+                Ok(PageUpdate::Changed { page, validators }) => (page, validators),
+                Ok(PageUpdate::Unchanged) => continue,
+                Err(err) => {
+                    eprintln!("could not fetch page {page_nr}: {err}");
+                    continue;
+                }
+            };
+            worker::complete(&source, cache.as_ref(), &mut page, Some(&validators));
+
+            if let Some((old, _)) = known.get(&page_nr) {
+                let mut out = io::stdout().lock();
+                writeln!(out, "{}", change_line(old, &page, &page_range, args.glyphs))?;
+                out.flush()?;
+                if let Some(command) = exec {
+                    if let Err(report) = run_hook(command, &page, &page_range, args.glyphs) {
+                        eprintln!("{report:#}");
+                    }
+                }
+            }
+            known.insert(page_nr, (page, validators));
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Runs the `get` subcommand: prints the pages in `range` to stdout in
+/// `format`.
+///
+/// # Errors
+///
+/// Will return `Err` if `range` is outside the page range of the backend, or
+/// if the pages cannot be fetched, parsed, or written.
+pub fn get(args: &Cli, range: PageRange, format: Format) -> Result<()> {
+    check_pages(args, range.lo, range.hi)?;
+    let cache = open_cache(args);
+    let source = source::for_backend(args.backend, &args.client_settings())?;
+    let pages = fetch_range(&source, cache.as_ref(), args.offline, range)?;
+    let page_range = args.backend.page_range();
+
+    let stdout = io::stdout();
+    let colour = match format {
+        Format::Auto => stdout.is_terminal(),
+        Format::Ansi => true,
+        Format::Text | Format::Json => false,
+    };
+    let mut out = stdout.lock();
+    if format == Format::Json {
+        for response in &pages {
+            print::write_json(&mut out, response, &page_range, args.glyphs)?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+    for (i, response) in pages.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        write_page(
+            &mut out,
+            response,
+            args.plain,
+            colour,
+            &page_range,
+            args.glyphs,
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texttv::PAGE_RANGE;
+    use clap::Parser;
+
+    fn test_page(rows: &[&str]) -> PageResponse {
+        let mut content = String::new();
+        for row in rows {
+            content.push_str(r#"<span class="line"><span class="bgBl W">"#);
+            content.push_str(row);
+            content.push_str("</span></span>");
+        }
+        PageResponse {
+            num: 377,
+            title: "Fotboll".into(),
+            content: vec![content],
+            content_plain: None,
+            next_page: 378,
+            prev_page: 376,
+            date_updated_unix: 1_710_006_120,
+            permalink: String::new(),
+            id: 1,
+            breadcrumbs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_check_pages() {
+        let args = Cli::parse_from(["textty", "--backend", "nrk"]);
+        assert!(check_pages(&args, 100, 799).is_ok());
+        assert!(check_pages(&args, 700, 850).is_err());
+        assert!(check_pages(&args, 99, 99).is_err());
+    }
+
+    #[test]
+    fn test_change_line() {
+        let old = test_page(&[" 377 SVT Text", " Malmö FF  3  7", " Hammarby  3  4"]);
+        let new = test_page(&[" 377 SVT Text", " Malmö FF  3  7", " Hammarby  4  7"]);
+        let line = change_line(&old, &new, &PAGE_RANGE, Glyphs::Sextant);
+        assert!(line.ends_with(" 377 Fotboll: Hammarby  4  7"), "{line}");
+        let line = change_line(&old, &old, &PAGE_RANGE, Glyphs::Sextant);
+        assert!(line.ends_with(" 377 Fotboll"), "{line}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let page = test_page(&[" 377 SVT Text", " Hammarby  4  7"]);
+        let command = format!(
+            r#"cat > "{0}/page.json"; echo "$TEXTTY_PAGE" > "{0}/page.txt""#,
+            dir.path().display()
+        );
+        run_hook(&command, &page, &PAGE_RANGE, Glyphs::Sextant).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.path().join("page.json")).unwrap()).unwrap();
+        assert_eq!(json["num"], 377);
+        assert_eq!(json["subpages"][0][1][0]["text"], " Hammarby  4  7");
+        let env = std::fs::read_to_string(dir.path().join("page.txt")).unwrap();
+        assert_eq!(env, "377\n");
+
+        // A command that does not read the page is fine, even if the page is
+        // larger than the pipe buffer, but a failing command is not.
+        let mut large = page.clone();
+        large.content = vec![page.content[0].clone(); 1000];
+        run_hook("exec 0<&-; true", &large, &PAGE_RANGE, Glyphs::Sextant).unwrap();
+        assert!(run_hook("exit 3", &page, &PAGE_RANGE, Glyphs::Sextant).is_err());
+    }
+}
@@ -0,0 +1,73 @@
+//! Line-by-line comparison of parsed pages, to show what changed when a page
+//! is updated.
+
+use crate::page::{Page, Span};
+
+/// The indices of the lines that differ between `old` and `new`, in text or
+/// style. Lines that only one of the pages has count as changed.
+#[must_use]
+pub fn changed_lines(old: &Page, new: &Page) -> Vec<usize> {
+    (0..old.lines.len().max(new.lines.len()))
+        .filter(|&i| old.lines.get(i) != new.lines.get(i))
+        .collect()
+}
+
+/// The indices of the lines that differ between each page in the page sets
+/// `old` and `new`. Pages that only `new` has are changed on every line.
+#[must_use]
+pub fn changed_page_set(old: &[Page], new: &[Page]) -> Vec<Vec<usize>> {
+    let empty = Page::default();
+    new.iter()
+        .enumerate()
+        .map(|(i, page)| changed_lines(old.get(i).unwrap_or(&empty), page))
+        .collect()
+}
+
+/// The text of `line`, without styles.
+#[must_use]
+pub fn line_text(line: &[Span]) -> String {
+    line.iter().map(|span| span.content.as_str()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page;
+    use crate::texttv::PAGE_RANGE;
+
+    #[test]
+    fn test_changed_lines() {
+        let line = |class: &str, text: &str| {
+            format!(r#"<span class="line"><span class="{class}">{text}</span></span>"#)
+        };
+        let old = page::parse(
+            &[
+                line("bgBl W", "Malmö FF    3  7"),
+                line("bgBl W", "AIK         3  6"),
+                line("bgBl W", "Hammarby    3  4"),
+            ]
+            .concat(),
+            &PAGE_RANGE,
+        )
+        .unwrap();
+        let new = || {
+            page::parse(
+                &[
+                    line("bgBl W", "Malmö FF    3  7"),
+                    line("bgBl Y", "AIK         3  6"),
+                    line("bgBl W", "Hammarby    4  7"),
+                    line("bgBl W", "Djurgården  4  5"),
+                ]
+                .concat(),
+                &PAGE_RANGE,
+            )
+            .unwrap()
+        };
+        assert_eq!(changed_lines(&old, &new()), [1, 2, 3]);
+        assert_eq!(line_text(&new().lines[2]), "Hammarby    4  7");
+        assert_eq!(
+            changed_page_set(&[old], &[new(), new()]),
+            [vec![1, 2, 3], vec![0, 1, 2, 3]]
+        );
+    }
+}
@@ -10,6 +10,14 @@ pub enum Error {
     InvalidPageRange { lo: u16, hi: u16 },
     #[error(transparent)]
     IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("invalid mosaic image: {0}")]
+    Mosaic(String),
+    #[error("page {0} is not available offline")]
+    NotCached(u16),
+    #[error("not supported by the page source: {0}")]
+    Unsupported(&'static str),
     #[error("error parsing HTML: {0}")]
     ParseHtml(String),
 }
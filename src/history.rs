@@ -0,0 +1,136 @@
+/// A visited page, and the position within its page set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The page number.
+    pub page_nr: u16,
+
+    /// The page title.
+    pub title: String,
+
+    /// The index in the page set that was last displayed.
+    pub page_index: usize,
+}
+
+/// A browser-like history of visited pages.
+///
+/// Visiting a page after going back discards the entries ahead of the
+/// current position, like in a web browser.
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<Entry>,
+    position: usize,
+}
+
+impl History {
+    /// Record a visit to page `page_nr`. Revisiting the current page only
+    /// resets its page index.
+    pub fn visit(&mut self, page_nr: u16, title: &str) {
+        if let Some(current) = self.entries.get_mut(self.position) {
+            if current.page_nr == page_nr {
+                current.page_index = 0;
+                return;
+            }
+        }
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.position + 1);
+            self.position += 1;
+        }
+        self.entries.push(Entry {
+            page_nr,
+            title: title.to_string(),
+            page_index: 0,
+        });
+    }
+
+    /// Update the page index of the current entry.
+    pub fn set_page_index(&mut self, page_index: usize) {
+        if let Some(current) = self.entries.get_mut(self.position) {
+            current.page_index = page_index;
+        }
+    }
+
+    /// The position of the entry before the current entry, if any.
+    #[must_use]
+    pub const fn back(&self) -> Option<usize> {
+        self.position.checked_sub(1)
+    }
+
+    /// The position of the entry after the current entry, if any.
+    #[must_use]
+    pub const fn forward(&self) -> Option<usize> {
+        if self.position + 1 < self.entries.len() {
+            Some(self.position + 1)
+        } else {
+            None
+        }
+    }
+
+    /// Make the entry at `position` the current entry.
+    pub fn go_to(&mut self, position: usize) {
+        if position < self.entries.len() {
+            self.position = position;
+        }
+    }
+
+    /// The entry at `position`, if any.
+    #[must_use]
+    pub fn get(&self, position: usize) -> Option<&Entry> {
+        self.entries.get(position)
+    }
+
+    /// The position of the current entry.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+
+    /// All entries, oldest first.
+    #[must_use]
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_back_and_forward() {
+        let mut history = History::default();
+        assert_eq!(history.back(), None);
+        assert_eq!(history.forward(), None);
+
+        history.visit(100, "SVT Text");
+        history.visit(377, "Resultat");
+        history.set_page_index(2);
+        history.visit(401, "Väder");
+        assert_eq!(history.position(), 2);
+
+        let back = history.back().unwrap();
+        history.go_to(back);
+        let entry = history.get(history.position()).unwrap();
+        assert_eq!((entry.page_nr, entry.page_index), (377, 2));
+        assert_eq!(history.forward(), Some(2));
+
+        // Visiting a new page discards the forward entries.
+        history.visit(300, "Sport");
+        assert_eq!(history.forward(), None);
+        let pages = history
+            .entries()
+            .iter()
+            .map(|e| e.page_nr)
+            .collect::<Vec<_>>();
+        assert_eq!(pages, [100, 377, 300]);
+    }
+
+    #[test]
+    fn test_revisit_current_page() {
+        let mut history = History::default();
+        history.visit(100, "SVT Text");
+        history.set_page_index(1);
+        history.visit(100, "SVT Text");
+        assert_eq!(history.entries().len(), 1);
+        assert_eq!(history.get(0).unwrap().page_index, 0);
+    }
+}
@@ -1,6 +1,26 @@
+//! A TUI client for SVT Text-TV, and a library for fetching and parsing its
+//! pages from `texttv.nu`.
+//!
+//! Pages are fetched with [`texttv::Client`], or any other
+//! [`source::PageSource`], and parsed into the typed grid model in [`model`].
+
+mod bookmarks;
+pub mod cache;
 pub mod cli;
+pub mod commands;
+mod diff;
 mod error;
+mod history;
+pub mod logger;
+pub mod model;
 mod mosaic;
+pub mod nordic;
 mod page;
-mod texttv;
+mod print;
+pub mod source;
+pub mod svt;
+pub mod texttv;
 pub mod tui;
+mod worker;
+
+pub use error::Error;
@@ -0,0 +1,75 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A logger that appends records to a file, since the TUI owns the terminal.
+/// The file is only created once something is logged.
+struct FileLogger {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+        if file.is_none() {
+            if let Some(dir) = self.path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            *file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .ok();
+        }
+        if let Some(file) = file.as_mut() {
+            let _ = writeln!(
+                file,
+                "{} {:<5} {}",
+                chrono::Local::now().format("%Y-%m-%dT%H:%M:%S"),
+                record.level(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Log to `textty.log` in the user's cache directory, e.g.
+/// `~/.cache/textty/textty.log` on Linux. The level defaults to `warn`, and
+/// can be set with the `TEXTTY_LOG` environment variable, e.g. `TEXTTY_LOG=debug`
+/// or `TEXTTY_LOG=off`. Does nothing if the platform has no cache directory.
+pub fn init() {
+    let Some(dir) = dirs::cache_dir() else {
+        return;
+    };
+    let level = std::env::var("TEXTTY_LOG")
+        .ok()
+        .and_then(|level| level.parse().ok())
+        .unwrap_or(LevelFilter::Warn);
+    let logger = FileLogger {
+        path: dir.join("textty").join("textty.log"),
+        file: Mutex::new(None),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level);
+    }
+}
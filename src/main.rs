@@ -1,11 +1,34 @@
 use clap::Parser;
-use textty::cli::Cli;
+use ratatui::crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use ratatui::crossterm::execute;
+use std::io::stdout;
+use std::time::Duration;
+use textty::cli::{Cli, Command};
 use textty::tui::App;
+use textty::{commands, logger};
 
 fn main() -> color_eyre::Result<()> {
     let args = Cli::parse();
+    logger::init();
+    match args.command {
+        Some(Command::Get { pages, format }) => commands::get(&args, pages, format),
+        Some(Command::Diff { page }) => commands::diff(&args, page),
+        Some(Command::Watch {
+            ref pages,
+            interval,
+            ref exec,
+        }) => commands::watch(&args, pages, Duration::from_secs(interval), exec.as_deref()),
+        None => run_tui(&args),
+    }
+}
+
+fn run_tui(args: &Cli) -> color_eyre::Result<()> {
+    let app = App::new(args)?;
     let terminal = ratatui::init();
-    let result = App::new(args.plain).run(terminal);
+    let result = execute!(stdout(), EnableMouseCapture)
+        .map_err(color_eyre::Report::from)
+        .and_then(|()| app.run(terminal));
+    let _ = execute!(stdout(), DisableMouseCapture);
     ratatui::restore();
     result
 }
@@ -0,0 +1,297 @@
+//! A typed model of teletext pages, as a grid of [`COLUMNS`] × [`ROWS`] cells.
+//!
+//! The model is built from the parsed HTML of a [`PageResponse`], and is
+//! independent of how the page is displayed.
+//!
+//! ```no_run
+//! use textty::model::Page;
+//! use textty::texttv::Client;
+//!
+//! let page = Page::fetch(&Client::default(), 377)?;
+//! for row in &page.subpages[0].rows {
+//!     let text = row.cells.iter().map(|cell| cell.ch).collect::<String>();
+//!     println!("{text}");
+//! }
+//! # Ok::<(), textty::Error>(())
+//! ```
+
+use crate::error::Error;
+use crate::page;
+use crate::source::PageSource;
+use crate::texttv::PageResponse;
+use crate::worker;
+use serde::Serialize;
+use std::ops::RangeInclusive;
+
+pub use crate::page::{BgColour, FgColour};
+
+/// The number of cells in a row.
+pub const COLUMNS: usize = 40;
+
+/// The number of rows in a subpage.
+pub const ROWS: usize = 25;
+
+/// A teletext page, with one or more subpages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Page {
+    /// The page number.
+    pub num: u16,
+
+    /// The page title.
+    pub title: String,
+
+    /// The previous available page.
+    pub prev_page: u16,
+
+    /// The next available page.
+    pub next_page: u16,
+
+    /// A UNIX timestamp representing the time the page was last updated, or
+    /// 0 if unknown.
+    pub updated_unix: i64,
+
+    /// The subpages of the page, in order; there is always at least one.
+    pub subpages: Vec<Subpage>,
+}
+
+/// One screen of a page: exactly [`ROWS`] rows.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Subpage {
+    pub rows: Vec<Row>,
+}
+
+/// A row of a subpage: exactly [`COLUMNS`] cells.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Row {
+    pub cells: Vec<Cell>,
+
+    /// The row is drawn in double height, covering the row below it.
+    pub double_height: bool,
+
+    /// The row is the header row of the page.
+    pub top_row: bool,
+}
+
+/// A character cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Cell {
+    /// The character, or the block graphics glyph of a mosaic.
+    pub ch: char,
+    pub fg: FgColour,
+    pub bg: BgColour,
+
+    /// The kind of block graphics, if the cell is a mosaic.
+    pub mosaic: Option<MosaicKind>,
+
+    /// The page referenced by the cell, if it is part of a page number.
+    pub link: Option<u16>,
+}
+
+/// The kind of block graphics of a mosaic cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MosaicKind {
+    /// The 2×3 blocks touch each other.
+    Contiguous,
+    /// The 2×3 blocks have a gap around them.
+    Separated,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            ch: ' ',
+            fg: FgColour::default(),
+            bg: BgColour::default(),
+            mosaic: None,
+            link: None,
+        }
+    }
+}
+
+impl Row {
+    fn blank() -> Self {
+        Self {
+            cells: vec![Cell::default(); COLUMNS],
+            double_height: false,
+            top_row: false,
+        }
+    }
+
+    /// The text of the row, with mosaics as their glyphs.
+    #[must_use]
+    pub fn text(&self) -> String {
+        self.cells.iter().map(|cell| cell.ch).collect()
+    }
+}
+
+impl From<&page::Page> for Subpage {
+    /// Lay out a parsed page on the grid. Lines are cut at [`COLUMNS`] cells,
+    /// and blank cells and rows are added to fill the grid.
+    fn from(page: &page::Page) -> Self {
+        let mut rows = Vec::with_capacity(ROWS);
+        for (line, attrs) in page.lines.iter().zip(&page.attrs).take(ROWS) {
+            let mut row = Row::blank();
+            row.double_height = attrs.double_height;
+            row.top_row = attrs.top_row;
+            let chars = line.iter().flat_map(|span| {
+                let mosaic = if span.style.is_separated() {
+                    Some(MosaicKind::Separated)
+                } else if span.style.is_mosaic() {
+                    Some(MosaicKind::Contiguous)
+                } else {
+                    None
+                };
+                span.content.chars().map(move |ch| Cell {
+                    ch,
+                    fg: span.style.fg,
+                    bg: span.style.bg,
+                    mosaic,
+                    link: span.link,
+                })
+            });
+            for (cell, new) in row.cells.iter_mut().zip(chars) {
+                *cell = new;
+            }
+            rows.push(row);
+        }
+        rows.resize_with(ROWS, Row::blank);
+        Self { rows }
+    }
+}
+
+impl Page {
+    /// Fetch page `number` from `source`, and parse all its subpages. Mosaic
+    /// GIFs that are missing from the lookup table are fetched from `source`
+    /// and decoded.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the page cannot be fetched or parsed.
+    pub fn fetch(source: &impl PageSource, number: u16) -> Result<Self, Error> {
+        let response = source.get_page(number)?;
+        Self::from_response(&response, source)
+    }
+
+    /// Parse all subpages of `response`, which was fetched from `source`,
+    /// with links within the page range of `source` and the previous and
+    /// next page from `source`. Mosaic GIFs that are missing from the lookup
+    /// table are fetched from `source` and decoded.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the page cannot be parsed.
+    pub fn from_response(response: &PageResponse, source: &impl PageSource) -> Result<Self, Error> {
+        worker::resolve_mosaics(source, None, response, false);
+        let mut page = Self::parse(response, &source.page_range())?;
+        page.prev_page = source.prev_page(response);
+        page.next_page = source.next_page(response);
+        Ok(page)
+    }
+
+    /// Parse all subpages of `response`, with links to the page numbers in
+    /// `pages`. Mosaics that are missing from the lookup table are left
+    /// blank; see [`Page::from_response`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the page cannot be parsed.
+    pub fn parse(response: &PageResponse, pages: &RangeInclusive<u16>) -> Result<Self, Error> {
+        let subpages = response
+            .content
+            .iter()
+            .map(|content| page::parse(content, pages).map(|page| Subpage::from(&page)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if subpages.is_empty() {
+            return Err(Error::InvalidPageNumber(response.num));
+        }
+        Ok(Self {
+            num: response.num,
+            title: response.title.clone(),
+            prev_page: response.prev_page,
+            next_page: response.next_page,
+            updated_unix: response.date_updated_unix,
+            subpages,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nordic::Service;
+    use crate::source::MemorySource;
+    use crate::texttv::{self, test_page};
+
+    /// Pages in memory with the page range of NRK.
+    struct NrkSource(MemorySource);
+
+    impl PageSource for NrkSource {
+        fn get_page(&self, number: u16) -> Result<PageResponse, Error> {
+            self.0.get_page(number)
+        }
+
+        fn prev_page(&self, page: &PageResponse) -> u16 {
+            self.0.prev_page(page)
+        }
+
+        fn next_page(&self, page: &PageResponse) -> u16 {
+            self.0.next_page(page)
+        }
+
+        fn page_range(&self) -> RangeInclusive<u16> {
+            Service::Nrk.page_range()
+        }
+    }
+
+    #[test]
+    fn test_subpage_grid() {
+        let html = concat!(
+            r#"<span class="line DH"><span class="bgB Y">Sport</span><span class="bgBl W"> 300</span></span>"#,
+            r#"<span class="line"><span class="bgBl W">"#,
+            "0123456789012345678901234567890123456789 cut",
+            "</span></span>",
+        );
+        let subpage = Subpage::from(&page::parse(html, &texttv::PAGE_RANGE).unwrap());
+        assert_eq!(subpage.rows.len(), ROWS);
+        assert!(subpage.rows.iter().all(|row| row.cells.len() == COLUMNS));
+
+        let row = &subpage.rows[0];
+        assert!(row.double_height);
+        assert_eq!(row.text().trim_end(), "Sport 300");
+        assert_eq!(
+            row.cells[0],
+            Cell {
+                ch: 'S',
+                fg: FgColour::Yellow,
+                bg: BgColour::Blue,
+                mosaic: None,
+                link: None,
+            }
+        );
+        assert_eq!(row.cells[6].link, Some(300));
+        assert_eq!(
+            subpage.rows[1].text(),
+            "0123456789012345678901234567890123456789"
+        );
+        assert_eq!(subpage.rows[2], Row::blank());
+    }
+
+    #[test]
+    fn test_fetch_within_source_range() {
+        let mut page = test_page(100);
+        page.content = vec![
+            r#"<span class="line"><span class="bgBl W">Sport 300 Radio 850</span></span>"#.into(),
+        ];
+        let source = NrkSource([page, test_page(300)].into_iter().collect());
+
+        let page = Page::fetch(&source, 100).unwrap();
+        let links = page.subpages[0].rows[0]
+            .cells
+            .iter()
+            .filter_map(|cell| cell.link)
+            .collect::<Vec<_>>();
+        assert_eq!(links, [300, 300, 300]);
+        assert_eq!((page.prev_page, page.next_page), (300, 300));
+    }
+}
@@ -1,3 +1,22 @@
+use crate::error::Error;
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+
+/// The glyphs used to draw teletext block graphics (mosaics).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Glyphs {
+    /// Sextants (U+1FB00), which match the 2×3 mosaics exactly.
+    #[default]
+    Sextant,
+    /// Braille patterns (U+2800), which approximate the 2×3 mosaics with dots.
+    Braille,
+    /// Quadrant block elements (U+2580), with a 2×2 approximation.
+    Quadrant,
+    /// ASCII characters, with a rough approximation.
+    Ascii,
+}
+
 /* Mosaic characters available in the teletext character set
 [
     ' ', '🬀', '🬁', '🬂', '🬃', '🬄', '🬅', '🬆', '🬇', '🬈', '🬉', '🬊', '🬋', '🬌', '🬍', '🬎', '🬏', '🬐', '🬑',
@@ -7,45 +26,385 @@
 ]
 */
 
+/// Known texttv.nu mosaic GIFs, by ID. Each mosaic has one GIF per colour
+/// combination, so there are several IDs per glyph.
 #[allow(clippy::unreadable_literal)]
-pub const fn from_gif_id(id: u64) -> char {
-    match id {
-        // texttv.nu gifs
-        1164105659 | 251408512 | 3287848953 | 2335531887 | 2862847544 | 1739010369 => '🬞',
-        1219799629 | 2190446388 | 2693613557 | 2327991958 | 3987931972 | 1227236920 => '🬉',
-        1250598021 | 1326555685 | 1254105466 | 4249453864 | 4244846807 | 2790421332 | 299620102
-        | 294742777 => '🬭',
-        1339760422 | 3387636925 | 3826504151 | 1760051201 | 3288266310 | 2267014944 => '🬷',
-        1460303617 | 1625865678 | 3609107780 | 2681114375 | 1087885570 | 999369151 => '🬵',
-        1460540445 | 2537420265 | 2413702233 | 3771534768 | 207576990 | 3147580979 => '🬹',
-        1685294852 | 2913233310 | 3806973766 | 167497510 | 750680978 | 2296503594 => '🬻',
-        1994053858 | 2754943555 | 1091112751 | 2140796170 | 2594562150 => '🬓',
-        2156528839 | 2201328430 | 2642197907 | 2934086162 | 3352595016 | 4098534857
-        | 1270603014 | 2015754887 | 2964044975 => '▐',
-        2287478073 | 3138777730 | 3150678580 | 693852549 | 925899746 | 1840924899 | 3785335171 => {
-            '🬦'
-        }
-        3037313580 | 3782488817 | 4166044020 | 1028566380 | 880409429 | 3896730824 | 610948841 => {
-            '🬁'
-        }
-        3215696164 | 2353048447 | 3772511681 | 3838981461 | 739691859 => '🬱',
-        3585010416 | 15963642 | 2030688620 | 2509998914 | 3965831124 | 2762748738 | 3713433556 => {
-            '🬏'
-        }
-        2218724507 | 1559180511 | 872158518 | 723504262 => '🬋',
-        2308811616 => '🬠',
-        282174899 => '🬑',
-        2881270998 => '🬯',
-        3188198897 | 3547727352 => '🬇',
-        3298983629 => '🬫',
-        3618463797 => '🬃',
-        3931275958 => '🬜',
-        4082209591 => '🬅',
-        1118560998 => '🬩',
-        1056054768 | 225196657 => '🬘',
-        // texttv.nu quirks
-        692512409 => '*',
-        // default
-        _ => ' ',
+const fn from_gif_id(id: u64) -> Option<char> {
+    let c =
+        match id {
+            // texttv.nu gifs
+            1164105659 | 251408512 | 3287848953 | 2335531887 | 2862847544 | 1739010369 => '🬞',
+            1219799629 | 2190446388 | 2693613557 | 2327991958 | 3987931972 | 1227236920 => '🬉',
+            1250598021 | 1326555685 | 1254105466 | 4249453864 | 4244846807 | 2790421332
+            | 299620102 | 294742777 => '🬭',
+            1339760422 | 3387636925 | 3826504151 | 1760051201 | 3288266310 | 2267014944 => '🬷',
+            1460303617 | 1625865678 | 3609107780 | 2681114375 | 1087885570 | 999369151 => '🬵',
+            1460540445 | 2537420265 | 2413702233 | 3771534768 | 207576990 | 3147580979 => '🬹',
+            1685294852 | 2913233310 | 3806973766 | 167497510 | 750680978 | 2296503594 => '🬻',
+            1994053858 | 2754943555 | 1091112751 | 2140796170 | 2594562150 => '🬓',
+            2156528839 | 2201328430 | 2642197907 | 2934086162 | 3352595016 | 4098534857
+            | 1270603014 | 2015754887 | 2964044975 => '▐',
+            2287478073 | 3138777730 | 3150678580 | 693852549 | 925899746 | 1840924899
+            | 3785335171 => '🬦',
+            3037313580 | 3782488817 | 4166044020 | 1028566380 | 880409429 | 3896730824
+            | 610948841 => '🬁',
+            3215696164 | 2353048447 | 3772511681 | 3838981461 | 739691859 => '🬱',
+            3585010416 | 15963642 | 2030688620 | 2509998914 | 3965831124 | 2762748738
+            | 3713433556 => '🬏',
+            2218724507 | 1559180511 | 872158518 | 723504262 => '🬋',
+            2308811616 => '🬠',
+            282174899 => '🬑',
+            2881270998 => '🬯',
+            3188198897 | 3547727352 => '🬇',
+            3298983629 => '🬫',
+            3618463797 => '🬃',
+            3931275958 => '🬜',
+            4082209591 => '🬅',
+            1118560998 => '🬩',
+            1056054768 | 225196657 => '🬘',
+            // texttv.nu quirks
+            692512409 => '*',
+            _ => return None,
+        };
+    Some(c)
+}
+
+/// Mosaics decoded from their GIF at runtime, for IDs missing from the table.
+static DECODED: LazyLock<RwLock<HashMap<u64, char>>> = LazyLock::new(RwLock::default);
+
+/// The glyph for the mosaic GIF with `id`, if it is known or has been decoded.
+pub fn lookup(id: u64) -> Option<char> {
+    from_gif_id(id).or_else(|| DECODED.read().ok()?.get(&id).copied())
+}
+
+/// Remember `c` as the glyph for the mosaic GIF with `id`.
+pub fn register(id: u64, c: char) {
+    if let Ok(mut decoded) = DECODED.write() {
+        decoded.insert(id, c);
+    }
+}
+
+/// The glyphs of the mosaic GIFs decoded at runtime, by ID.
+pub fn decoded() -> HashMap<u64, char> {
+    DECODED
+        .read()
+        .map(|decoded| decoded.clone())
+        .unwrap_or_default()
+}
+
+/// The sextant glyph for a 2×3 mosaic, where bit 0 is the top left cell,
+/// bit 1 the top right cell, and so on down to bit 5, the bottom right cell.
+/// Only the lower 6 bits of `bits` are used.
+#[must_use]
+pub fn sextant(bits: u8) -> char {
+    // The sextant block omits the patterns that already exist as block
+    // elements: the empty cell, the left and right halves, and the full block.
+    let code = match bits & 0x3f {
+        0 => return ' ',
+        21 => return '▌',
+        42 => return '▐',
+        63 => return '█',
+        n @ 1..21 => u32::from(n) - 1,
+        n @ 22..42 => u32::from(n) - 2,
+        n => u32::from(n) - 3,
+    };
+    char::from_u32(0x1fb00 + code).unwrap_or(' ')
+}
+
+/// The separated mosaic glyph for a 2×3 mosaic, with the same bit order as
+/// [`sextant`]. Only the lower 6 bits of `bits` are used.
+#[must_use]
+pub fn separated(bits: u8) -> char {
+    match bits & 0x3f {
+        0 => ' ',
+        n => char::from_u32(0x1ce50 + u32::from(n)).unwrap_or(' '),
+    }
+}
+
+/// Returns `true` if `c` is a separated mosaic glyph.
+#[must_use]
+pub fn is_separated(c: char) -> bool {
+    ('\u{1ce51}'..='\u{1ce8f}').contains(&c)
+}
+
+/// The 2×3 mosaic bits of a contiguous or separated mosaic glyph; the inverse
+/// of [`sextant`] and [`separated`].
+#[must_use]
+pub fn bits(c: char) -> Option<u8> {
+    let bits = match c {
+        ' ' => 0,
+        '▌' => 21,
+        '▐' => 42,
+        '█' => 63,
+        '\u{1fb00}'..='\u{1fb3b}' => {
+            // Skip the patterns of the left and right halves; see `sextant`.
+            let n = u8::try_from(u32::from(c) - 0x1fb00).ok()? + 1;
+            match n {
+                1..21 => n,
+                21..=40 => n + 1,
+                _ => n + 2,
+            }
+        }
+        _ if is_separated(c) => u8::try_from(u32::from(c) - 0x1ce50).ok()?,
+        _ => return None,
+    };
+    Some(bits)
+}
+
+/// Draw the mosaic glyph `c` with `glyphs`, approximating the 2×3 cells if
+/// needed. Other characters are returned unchanged.
+#[must_use]
+pub fn convert(c: char, glyphs: Glyphs) -> char {
+    let Some(bits) = bits(c) else {
+        return c;
+    };
+    match glyphs {
+        Glyphs::Sextant => c,
+        Glyphs::Braille => braille(bits),
+        Glyphs::Quadrant => QUADRANTS[usize::from(quadrant(bits))],
+        Glyphs::Ascii => ASCII[usize::from(quadrant(bits))],
+    }
+}
+
+/// The braille pattern for a 2×3 mosaic. The bottom cells also set the
+/// fourth row of dots, so that the pattern fills the character cell.
+fn braille(bits: u8) -> char {
+    // Braille dots 1-3 are the left column, top to bottom, and dots 4-6 the
+    // right column; dots 7 and 8 are the bottom row.
+    const DOTS: [u32; 6] = [0x01, 0x08, 0x02, 0x10, 0x04 | 0x40, 0x20 | 0x80];
+    if bits == 0 {
+        return ' ';
+    }
+    let dots = (0..6)
+        .filter(|cell| bits & 1 << cell != 0)
+        .fold(0, |dots, cell| dots | DOTS[cell]);
+    char::from_u32(0x2800 + dots).unwrap_or(' ')
+}
+
+/// The upper or lower half of the mosaic glyph `c` drawn in double height,
+/// drawn with `glyphs`. The half is taken from the 2×3 cells of `c`, so `c`
+/// must not have been converted already. Other characters are returned
+/// unchanged.
+#[must_use]
+pub fn double_height(c: char, lower: bool, glyphs: Glyphs) -> char {
+    let Some(bits) = bits(c) else {
+        return c;
+    };
+    // Each of the 3 rows is stretched over 2 rows; the upper half shows the
+    // top, top and middle rows, and the lower half the middle, bottom and
+    // bottom rows.
+    let row = |n: u8| (bits >> (2 * n)) & 0b11;
+    let rows = if lower {
+        [row(1), row(2), row(2)]
+    } else {
+        [row(0), row(0), row(1)]
+    };
+    let half = rows[0] | rows[1] << 2 | rows[2] << 4;
+    let half = if is_separated(c) {
+        separated(half)
+    } else {
+        sextant(half)
+    };
+    convert(half, glyphs)
+}
+
+/// Quadrant block elements, indexed by the bits of [`quadrant`].
+const QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+/// ASCII approximations, indexed by the bits of [`quadrant`].
+const ASCII: [char; 16] = [
+    ' ', '`', '\'', '"', ',', '[', '/', '#', '.', '\\', ']', '#', '_', '#', '#', '#',
+];
+
+/// Approximate a 2×3 mosaic as a 2×2 mosaic, where bit 0 is the top left
+/// quadrant, bit 1 the top right, bit 2 the bottom left, and bit 3 the bottom
+/// right. A middle cell only counts if its column is otherwise empty.
+fn quadrant(bits: u8) -> u8 {
+    let mut quadrant = 0;
+    for column in 0..2 {
+        let [top, middle, bottom] = [0, 2, 4].map(|row| bits & 1 << (row + column) != 0);
+        let middle_only = middle && !top && !bottom;
+        if top || middle_only {
+            quadrant |= 1 << column;
+        }
+        if bottom {
+            quadrant |= 1 << (column + 2);
+        }
+    }
+    quadrant
+}
+
+/// A mosaic decoded from a GIF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decoded {
+    /// The 2×3 cells that are set, in the bit order of [`sextant`].
+    pub bits: u8,
+
+    /// Whether the cells are drawn with a gap around them.
+    pub separated: bool,
+}
+
+impl Decoded {
+    /// The glyph for the mosaic.
+    #[must_use]
+    pub fn glyph(self) -> char {
+        if self.separated {
+            separated(self.bits)
+        } else {
+            sextant(self.bits)
+        }
+    }
+}
+
+/// Decode a texttv.nu mosaic GIF, drawn with the foreground colour `fg` on
+/// the background colour `bg`.
+///
+/// The image is split into a 2×3 grid of cells, and a cell is set if enough
+/// of its pixels are closer to `fg` than to `bg`. The mosaic is separated if
+/// the set cells are only partly covered.
+///
+/// # Errors
+///
+/// Will return `Err` if `bytes` is not a valid GIF image.
+pub fn decode_gif(bytes: &[u8], fg: [u8; 3], bg: [u8; 3]) -> Result<Decoded, Error> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options
+        .read_info(bytes)
+        .map_err(|err| Error::Mosaic(err.to_string()))?;
+    let frame = decoder
+        .read_next_frame()
+        .map_err(|err| Error::Mosaic(err.to_string()))?
+        .ok_or_else(|| Error::Mosaic("no image data".into()))?;
+
+    let (width, height) = (usize::from(frame.width), usize::from(frame.height));
+    if width < 2 || height < 3 {
+        return Err(Error::Mosaic(format!("image too small: {width}x{height}")));
+    }
+    let distance = |a: &[u8], b: [u8; 3]| -> u32 {
+        a.iter()
+            .zip(b)
+            .map(|(&x, y)| u32::from(x.abs_diff(y)).pow(2))
+            .sum()
+    };
+    let mut lit = [0usize; 6];
+    let mut total = [0usize; 6];
+    for (i, pixel) in frame.buffer.chunks_exact(4).enumerate() {
+        let (x, y) = (i % width, i / width);
+        let cell = (y * 3 / height) * 2 + x * 2 / width;
+        total[cell] += 1;
+        // Transparent pixels show the background.
+        if pixel[3] > 0 && distance(&pixel[..3], fg) < distance(&pixel[..3], bg) {
+            lit[cell] += 1;
+        }
+    }
+    // Separated mosaics leave a gap around each cell, so a cell only needs to
+    // be partly covered to count as set.
+    let set = (0..6).filter(|&cell| lit[cell] * 3 > total[cell]);
+    let bits = set.clone().fold(0, |bits, cell| bits | 1 << cell);
+    let (lit, total) = set.fold((0, 0), |(l, t), cell| (l + lit[cell], t + total[cell]));
+    Ok(Decoded {
+        bits,
+        separated: lit * 10 < total * 9,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sextant() {
+        // The table of all 64 mosaics, in order.
+        let glyphs = (0..64).map(sextant).collect::<String>();
+        assert_eq!(
+            glyphs,
+            " 🬀🬁🬂🬃🬄🬅🬆🬇🬈🬉🬊🬋🬌🬍🬎🬏🬐🬑🬒🬓▌🬔🬕🬖🬗🬘🬙🬚🬛🬜🬝🬞🬟🬠🬡🬢🬣🬤🬥🬦🬧▐🬨🬩🬪🬫🬬🬭🬮🬯🬰🬱🬲🬳🬴🬵🬶🬷🬸🬹🬺🬻█"
+        );
+    }
+
+    /// Encode a 2-colour GIF, where `set(x, y)` selects the `fg` pixels.
+    fn encode_gif(fg: [u8; 3], bg: [u8; 3], set: impl Fn(u16, u16) -> bool) -> Vec<u8> {
+        let (width, height) = (8, 12);
+        let mut pixels = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(u8::from(!set(x, y)));
+            }
+        }
+        let mut bytes = Vec::new();
+        let palette = [fg, bg].concat();
+        let mut encoder = gif::Encoder::new(&mut bytes, width, height, &palette).unwrap();
+        let frame = gif::Frame::from_indexed_pixels(width, height, pixels, None);
+        encoder.write_frame(&frame).unwrap();
+        drop(encoder);
+        bytes
+    }
+
+    #[test]
+    fn test_bits() {
+        for n in 0..64 {
+            assert_eq!(bits(sextant(n)), Some(n));
+            assert_eq!(bits(separated(n)), Some(n));
+        }
+        assert_eq!(bits('*'), None);
+    }
+
+    #[test]
+    fn test_convert() {
+        let left = sextant(0b01_0101);
+        assert_eq!(convert(left, Glyphs::Sextant), '▌');
+        assert_eq!(convert(left, Glyphs::Braille), '⡇');
+        assert_eq!(convert(left, Glyphs::Quadrant), '▌');
+        assert_eq!(convert(left, Glyphs::Ascii), '[');
+
+        // A middle row becomes the upper half.
+        let middle = separated(0b00_1100);
+        assert_eq!(convert(middle, Glyphs::Quadrant), '▀');
+        assert_eq!(convert('*', Glyphs::Ascii), '*');
+    }
+
+    #[test]
+    fn test_double_height() {
+        // The top row and the bottom left cell.
+        let c = sextant(0b01_0011);
+        assert_eq!(double_height(c, false, Glyphs::Sextant), sextant(0b00_1111));
+        assert_eq!(double_height(c, true, Glyphs::Sextant), sextant(0b01_0100));
+        assert_eq!(double_height('A', true, Glyphs::Sextant), 'A');
+
+        // Other glyphs draw the halves, not the whole glyph on both rows.
+        for glyphs in [Glyphs::Braille, Glyphs::Quadrant, Glyphs::Ascii] {
+            let upper = double_height(c, false, glyphs);
+            assert_eq!(upper, convert(sextant(0b00_1111), glyphs));
+            assert_ne!(upper, double_height(c, true, glyphs));
+        }
+    }
+
+    #[test]
+    fn test_decode_gif() {
+        // The top left and bottom right cells set.
+        let (fg, bg) = ([255, 255, 0], [0, 0, 255]);
+        let bytes = encode_gif(fg, bg, |x, y| (y < 4 && x < 4) || (y >= 8 && x >= 4));
+        let decoded = decode_gif(&bytes, fg, bg).unwrap();
+        assert_eq!(
+            decoded,
+            Decoded {
+                bits: 0b10_0001,
+                separated: false
+            }
+        );
+        assert_eq!(decoded.glyph(), '🬟');
+
+        // The same cells, with a one pixel gap to the left and below each cell.
+        let bytes = encode_gif(fg, bg, |x, y| {
+            let set = (y < 4 && x < 4) || (y >= 8 && x >= 4);
+            set && x % 4 != 0 && y % 4 != 3
+        });
+        let decoded = decode_gif(&bytes, fg, bg).unwrap();
+        assert!(decoded.separated);
+        assert_eq!(decoded.glyph(), '\u{1ce71}');
+        assert!(is_separated(decoded.glyph()));
     }
 }
@@ -0,0 +1,453 @@
+//! Clients for the Nordic teletext services: Finnish YLE Teksti-TV, Danish DR
+//! Tekst-TV and Norwegian NRK Tekst-TV.
+//!
+//! The services publish each page as an HTML document, with the page text in
+//! a `<pre>` element and colours given by `style` or `color` attributes. The
+//! text is usually decoded to Unicode already, but some pages keep the raw
+//! national option character set of the service, where e.g. `[` is `Ä` in
+//! Finnish and `Æ` in Danish and Norwegian. Pages are normalised like those of
+//! [`crate::svt`], but keep their colours.
+
+use crate::error::Error;
+use crate::page::{BgColour, FgColour};
+use crate::source::PageSource;
+use crate::svt::{content_id, escape_html};
+use crate::texttv::{ClientBuilder, Http, PageResponse};
+use scraper::{ElementRef, Html, Node, Selector};
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+/// A national option character set, that replaces some ASCII characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// Swedish and Finnish, e.g. `[\]` as `ÄÖÅ`.
+    SwedishFinnish,
+    /// Danish and Norwegian, e.g. `[\]` as `ÆØÅ`.
+    DanishNorwegian,
+}
+
+impl Charset {
+    /// Decode the character `c` of the character set.
+    #[must_use]
+    pub const fn decode(self, c: char) -> char {
+        match (self, c) {
+            (_, '$') => '¤',
+            (_, '@') => 'É',
+            (_, '^') => 'Ü',
+            (_, '`') => 'é',
+            (_, '~') => 'ü',
+            (Self::SwedishFinnish, '[') => 'Ä',
+            (Self::SwedishFinnish, '\\') => 'Ö',
+            (Self::SwedishFinnish, '{') => 'ä',
+            (Self::SwedishFinnish, '|') => 'ö',
+            (Self::DanishNorwegian, '[') => 'Æ',
+            (Self::DanishNorwegian, '\\') => 'Ø',
+            (Self::DanishNorwegian, '{') => 'æ',
+            (Self::DanishNorwegian, '|') => 'ø',
+            (_, ']') => 'Å',
+            (_, '}') => 'å',
+            _ => c,
+        }
+    }
+}
+
+/// A Nordic teletext service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    /// Finnish YLE Teksti-TV.
+    Yle,
+    /// Danish DR Tekst-TV.
+    Dr,
+    /// Norwegian NRK Tekst-TV.
+    Nrk,
+}
+
+impl Service {
+    /// The name of the service.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Yle => "YLE Teksti-TV",
+            Self::Dr => "DR Tekst-TV",
+            Self::Nrk => "NRK Tekst-TV",
+        }
+    }
+
+    /// The URL of page `number`.
+    #[must_use]
+    pub fn page_url(self, number: u16) -> String {
+        match self {
+            Self::Yle => format!("https://yle.fi/tekstitv/txt/P{number}_01.html"),
+            Self::Dr => format!("https://www.dr.dk/cgi-bin/fttv1.exe/{number}"),
+            Self::Nrk => format!("https://www.nrk.no/tekst-tv/{number}"),
+        }
+    }
+
+    /// The range of page numbers of the service.
+    #[must_use]
+    pub const fn page_range(self) -> RangeInclusive<u16> {
+        match self {
+            Self::Yle | Self::Dr => 100..=899,
+            Self::Nrk => 100..=799,
+        }
+    }
+
+    /// The first page of the service.
+    #[must_use]
+    pub const fn home_page(self) -> u16 {
+        100
+    }
+
+    /// The character set of the page text.
+    #[must_use]
+    pub const fn charset(self) -> Charset {
+        match self {
+            Self::Yle => Charset::SwedishFinnish,
+            Self::Dr | Self::Nrk => Charset::DanishNorwegian,
+        }
+    }
+
+    /// The previous page before `number`, wrapping around to the last page.
+    const fn prev(self, number: u16) -> u16 {
+        let range = self.page_range();
+        if number <= *range.start() {
+            *range.end()
+        } else {
+            number - 1
+        }
+    }
+
+    /// The next page after `number`, wrapping around to the first page.
+    const fn next(self, number: u16) -> u16 {
+        let range = self.page_range();
+        if number >= *range.end() {
+            *range.start()
+        } else {
+            number + 1
+        }
+    }
+}
+
+/// Parse a CSS or HTML colour, e.g. `#ff0`, `#ffff00`, `rgb(255, 255, 0)`
+/// or `yellow`, as RGB.
+fn parse_rgb(value: &str) -> Option<[u8; 3]> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits = hex
+            .chars()
+            .map(|c| c.to_digit(16).and_then(|d| u8::try_from(d).ok()))
+            .collect::<Option<Vec<_>>>()?;
+        return match digits[..] {
+            [r, g, b] => Some([r * 17, g * 17, b * 17]),
+            [r1, r0, g1, g0, b1, b0] => Some([r1 * 16 + r0, g1 * 16 + g0, b1 * 16 + b0]),
+            _ => None,
+        };
+    }
+    if let Some(args) = value
+        .strip_prefix("rgb(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let channels = args
+            .split(',')
+            .map(|channel| channel.trim().parse().ok())
+            .collect::<Option<Vec<u8>>>()?;
+        return channels.try_into().ok();
+    }
+    let rgb = match value.as_str() {
+        "black" => [0, 0, 0],
+        "red" => [255, 0, 0],
+        "green" | "lime" => [0, 255, 0],
+        "yellow" => [255, 255, 0],
+        "blue" => [0, 0, 255],
+        "magenta" | "fuchsia" => [255, 0, 255],
+        "cyan" | "aqua" => [0, 255, 255],
+        "white" => [255, 255, 255],
+        _ => return None,
+    };
+    Some(rgb)
+}
+
+/// The teletext colour closest to `rgb`.
+fn nearest<C: Copy>(colours: &[C], rgb_of: impl Fn(C) -> [u8; 3], rgb: [u8; 3]) -> C {
+    let distance = |c: C| -> u32 {
+        rgb_of(c)
+            .iter()
+            .zip(rgb)
+            .map(|(&x, y)| u32::from(x.abs_diff(y)).pow(2))
+            .sum()
+    };
+    colours
+        .iter()
+        .copied()
+        .min_by_key(|&c| distance(c))
+        .unwrap_or(colours[0])
+}
+
+const FG_COLOURS: [FgColour; 8] = [
+    FgColour::White,
+    FgColour::Black,
+    FgColour::Blue,
+    FgColour::Cyan,
+    FgColour::Green,
+    FgColour::Magenta,
+    FgColour::Red,
+    FgColour::Yellow,
+];
+
+const BG_COLOURS: [BgColour; 8] = [
+    BgColour::Black,
+    BgColour::Blue,
+    BgColour::Cyan,
+    BgColour::Green,
+    BgColour::Magenta,
+    BgColour::Red,
+    BgColour::White,
+    BgColour::Yellow,
+];
+
+/// The colours of `element`, from a `style` attribute with `color` and
+/// `background-color`, or from `color` and `bgcolor` attributes.
+fn element_colours(element: ElementRef) -> (Option<FgColour>, Option<BgColour>) {
+    let mut fg = element.attr("color").and_then(parse_rgb);
+    let mut bg = element.attr("bgcolor").and_then(parse_rgb);
+    for declaration in element.attr("style").unwrap_or_default().split(';') {
+        let Some((property, value)) = declaration.split_once(':') else {
+            continue;
+        };
+        match property.trim() {
+            "color" => fg = parse_rgb(value).or(fg),
+            "background-color" | "background" => bg = parse_rgb(value).or(bg),
+            _ => {}
+        }
+    }
+    (
+        fg.map(|rgb| nearest(&FG_COLOURS, FgColour::rgb, rgb)),
+        bg.map(|rgb| nearest(&BG_COLOURS, BgColour::rgb, rgb)),
+    )
+}
+
+/// A run of text in one style.
+type Run = (String, FgColour, BgColour);
+
+/// Split the text of `pre` into lines of styled runs. Each run takes its
+/// colours from the closest enclosing elements that set them, and the text is
+/// decoded from `charset`, if any.
+fn styled_lines(pre: ElementRef, charset: Option<Charset>) -> Vec<Vec<Run>> {
+    let mut lines: Vec<Vec<Run>> = vec![Vec::new()];
+    for node in pre.descendants() {
+        let Node::Text(text) = node.value() else {
+            continue;
+        };
+        let (mut fg, mut bg) = (None, None);
+        for ancestor in node.ancestors().filter_map(ElementRef::wrap) {
+            let colours = element_colours(ancestor);
+            fg = fg.or(colours.0);
+            bg = bg.or(colours.1);
+            if ancestor == pre {
+                break;
+            }
+        }
+        let (fg, bg) = (fg.unwrap_or_default(), bg.unwrap_or_default());
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Vec::new());
+            }
+            if part.is_empty() {
+                continue;
+            }
+            let part = match charset {
+                Some(charset) => part.chars().map(|c| charset.decode(c)).collect(),
+                None => part.to_string(),
+            };
+            let line = lines.last_mut().expect("lines is never empty");
+            match line.last_mut() {
+                Some(last) if (last.1, last.2) == (fg, bg) => last.0.push_str(&part),
+                _ => line.push((part, fg, bg)),
+            }
+        }
+    }
+    // The text usually ends with a newline before `</pre>`.
+    if lines.last().is_some_and(Vec::is_empty) {
+        lines.pop();
+    }
+    lines
+}
+
+/// Lay out styled lines as `texttv.nu` HTML.
+fn to_html(lines: &[Vec<Run>]) -> String {
+    let mut html = String::new();
+    for line in lines {
+        html.push_str(r#"<span class="line">"#);
+        for (text, fg, bg) in line {
+            let _ = write!(
+                html,
+                r#"<span class="{} {}">{}</span>"#,
+                bg.class(),
+                fg.class(),
+                escape_html(text)
+            );
+        }
+        html.push_str("</span>");
+    }
+    html
+}
+
+/// Parse page `number` of `service` from its HTML document.
+///
+/// # Errors
+///
+/// Will return `Err` if the document has no `<pre>` element with the page.
+pub fn parse_page(service: Service, number: u16, html: &str) -> Result<PageResponse, Error> {
+    let document = Html::parse_document(html);
+    let Ok(selector) = Selector::parse("pre") else {
+        return Err(Error::ParseHtml("invalid selector".into()));
+    };
+    let Some(pre) = document.select(&selector).next() else {
+        return Err(Error::ParseHtml(format!(
+            "no page text in {} page {number}",
+            service.name()
+        )));
+    };
+
+    // Text with characters outside ASCII is already decoded, and decoding it
+    // again would replace e.g. the brackets and `@` of the page.
+    let raw = pre.text().all(str::is_ascii);
+    let lines = styled_lines(pre, raw.then(|| service.charset()));
+    let plain = lines
+        .iter()
+        .map(|line| {
+            line.iter()
+                .map(|(text, _, _)| text.as_str())
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(PageResponse {
+        num: number,
+        title: format!("{} {number}", service.name()),
+        content: vec![to_html(&lines)],
+        id: content_id(&[&plain]),
+        content_plain: Some(vec![plain]),
+        next_page: service.next(number),
+        prev_page: service.prev(number),
+        date_updated_unix: 0,
+        permalink: service.page_url(number),
+        breadcrumbs: Vec::new(),
+    })
+}
+
+/// A client for a Nordic teletext service.
+#[derive(Debug)]
+pub struct NordicClient {
+    service: Service,
+    http: Http,
+}
+
+impl NordicClient {
+    /// Create a client for `service`, with the HTTP settings of `settings`, as
+    /// described on [`ClientBuilder`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the proxy URL is invalid.
+    pub fn new(service: Service, settings: &ClientBuilder) -> Result<Self, Error> {
+        Ok(Self {
+            service,
+            http: settings.http()?,
+        })
+    }
+}
+
+impl PageSource for NordicClient {
+    fn get_page(&self, number: u16) -> Result<PageResponse, Error> {
+        let range = self.service.page_range();
+        let number = number.clamp(*range.start(), *range.end());
+        let url = self.service.page_url(number);
+        let html = self
+            .http
+            .send(|agent| agent.get(&url).call()?.body_mut().read_to_string())?;
+        parse_page(self.service, number, &html)
+    }
+
+    fn page_range(&self) -> RangeInclusive<u16> {
+        self.service.page_range()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page;
+
+    fn page_text(page: &page::Page, line: usize) -> String {
+        page.lines[line]
+            .iter()
+            .map(|span| span.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_yle() {
+        let html = include_str!("../tests/fixtures/yle/100.html");
+        let response = parse_page(Service::Yle, 100, html).unwrap();
+        assert_eq!(response.title, "YLE Teksti-TV 100");
+        assert_eq!((response.prev_page, response.next_page), (899, 101));
+        assert_eq!(response.updated(), None);
+
+        let page = page::parse(&response.content[0], &Service::Yle.page_range()).unwrap();
+        assert_eq!(page.lines.len(), 5);
+        let heading = &page.lines[1][0];
+        assert_eq!(
+            (heading.style.fg, heading.style.bg),
+            (FgColour::Yellow, BgColour::Blue)
+        );
+        assert_eq!(page_text(&page, 3), " Järjestys ja sää 400");
+        assert_eq!(page.lines[4][0].style.fg, FgColour::Cyan);
+        let targets = page.links.iter().map(|l| l.target).collect::<Vec<_>>();
+        assert_eq!(targets, [100, 102, 130, 400, 200]);
+    }
+
+    #[test]
+    fn test_parse_dr() {
+        let html = include_str!("../tests/fixtures/dr/100.html");
+        let response = parse_page(Service::Dr, 100, html).unwrap();
+        let page = page::parse(&response.content[0], &Service::Dr.page_range()).unwrap();
+        let heading = &page.lines[1][0];
+        assert_eq!(
+            (heading.style.fg, heading.style.bg),
+            (FgColour::Red, BgColour::White)
+        );
+        assert_eq!(page_text(&page, 4), " København og Århus ");
+    }
+
+    #[test]
+    fn test_parse_nrk() {
+        let html = include_str!("../tests/fixtures/nrk/100.html");
+        let response = parse_page(Service::Nrk, 799, html).unwrap();
+        assert_eq!(response.next_page, 100);
+        let page = page::parse(&response.content[0], &Service::Nrk.page_range()).unwrap();
+        assert_eq!(page.lines[1][0].style.fg, FgColour::Green);
+        assert_eq!(page_text(&page, 2), " Sport 300  Vær 400");
+        assert_eq!(page_text(&page, 3), " Tromsø og Finnmark 1 000 m");
+    }
+
+    #[test]
+    fn test_parse_decoded_text() {
+        let html = include_str!("../tests/fixtures/yle/101.html");
+        let response = parse_page(Service::Yle, 101, html).unwrap();
+        let page = page::parse(&response.content[0], &Service::Yle.page_range()).unwrap();
+        assert!(page_text(&page, 1).ends_with("[1/2] "));
+        assert_eq!(page_text(&page, 2), " Äänestysaktiivisuus nousi 102");
+        assert_eq!(page_text(&page, 3), " Lisää: yle.fi/uutiset ~ info@yle.fi");
+        assert_eq!(page_text(&page, 4), " Hinta {5 €} ^ $ \\ `");
+    }
+
+    #[test]
+    fn test_parse_rgb() {
+        assert_eq!(parse_rgb("#ff0"), Some([255, 255, 0]));
+        assert_eq!(parse_rgb("#00FF00"), Some([0, 255, 0]));
+        assert_eq!(parse_rgb("rgb(0, 0, 255)"), Some([0, 0, 255]));
+        assert_eq!(parse_rgb("Aqua"), Some([0, 255, 255]));
+        assert_eq!(parse_rgb("#ff"), None);
+    }
+}
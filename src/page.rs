@@ -1,19 +1,104 @@
 use crate::error::Error;
-use crate::mosaic;
+use crate::mosaic::{self, Glyphs};
 use scraper::{Html, Selector};
+use serde::Serialize;
+use std::ops::RangeInclusive;
 use std::str::FromStr;
 
-#[derive(Debug)]
+/// A parsed page: lines of styled spans, and the page references found in them.
+#[derive(Debug, Default)]
+pub struct Page {
+    pub lines: Vec<Vec<Span>>,
+    /// The attributes of each line in `lines`.
+    pub attrs: Vec<LineAttrs>,
+    pub links: Vec<Link>,
+}
+
+/// Attributes of a line, from the class of its HTML element, e.g. `line DH`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineAttrs {
+    /// The line is drawn in double height, covering the line below it.
+    pub double_height: bool,
+
+    /// The line is the header row of the page.
+    pub top_row: bool,
+}
+
+impl FromStr for LineAttrs {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut attrs = Self::default();
+        for class in s.split_whitespace() {
+            match class {
+                "DH" => attrs.double_height = true,
+                "toprow" => attrs.top_row = true,
+                _ => {}
+            }
+        }
+        Ok(attrs)
+    }
+}
+
+/// A row of a page on screen: a line, or the upper or lower half of a
+/// double-height line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayRow {
+    Single(usize),
+    Upper(usize),
+    Lower(usize),
+}
+
+impl DisplayRow {
+    /// The index of the displayed line.
+    #[must_use]
+    pub const fn line(self) -> usize {
+        match self {
+            Self::Single(line) | Self::Upper(line) | Self::Lower(line) => line,
+        }
+    }
+}
+
+/// A reference to another page, e.g. "Inrikes 101", located at
+/// `lines[line][span]` in a [`Page`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link {
+    pub line: usize,
+    pub span: usize,
+    pub target: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Span {
+    #[serde(flatten)]
     pub style: SpanStyle,
+    #[serde(rename = "text")]
     pub content: String,
+    /// The page referenced by the span, if the span is a page number.
+    pub link: Option<u16>,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Serialize)]
 pub struct SpanStyle {
     pub bg: BgColour,
     pub fg: FgColour,
     mosaic: bool,
+    /// Whether a mosaic is drawn as separated, rather than contiguous, blocks.
+    separated: bool,
+}
+
+impl SpanStyle {
+    /// Returns `true` if the span is a mosaic, rather than text.
+    #[must_use]
+    pub const fn is_mosaic(&self) -> bool {
+        self.mosaic
+    }
+
+    /// Returns `true` if the span is a separated mosaic.
+    #[must_use]
+    pub const fn is_separated(&self) -> bool {
+        self.separated
+    }
 }
 
 impl Default for SpanStyle {
@@ -22,6 +107,7 @@ impl Default for SpanStyle {
             bg: BgColour::Black,
             fg: FgColour::White,
             mosaic: false,
+            separated: false,
         }
     }
 }
@@ -40,6 +126,7 @@ impl FromStr for SpanStyle {
                     bg,
                     fg: FgColour::default(),
                     mosaic: false,
+                    separated: false,
                 })
             }
             [s0, s1] => {
@@ -49,6 +136,7 @@ impl FromStr for SpanStyle {
                     bg,
                     fg,
                     mosaic: false,
+                    separated: false,
                 })
             }
             [s0, s1, "bgImg"] => {
@@ -58,6 +146,7 @@ impl FromStr for SpanStyle {
                     bg,
                     fg,
                     mosaic: true,
+                    separated: false,
                 })
             }
             _ => Err(Error::ParseHtml(format!("invalid svt colour class: {s}"))),
@@ -65,7 +154,8 @@ impl FromStr for SpanStyle {
     }
 }
 
-#[derive(PartialEq, Eq, Debug, Default)]
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum BgColour {
     #[default]
     Black,
@@ -78,6 +168,38 @@ pub enum BgColour {
     Yellow,
 }
 
+impl BgColour {
+    /// The colour as RGB, as used by teletext.
+    #[must_use]
+    pub const fn rgb(self) -> [u8; 3] {
+        match self {
+            Self::Black => [0, 0, 0],
+            Self::Blue => [0, 0, 255],
+            Self::Cyan => [0, 255, 255],
+            Self::Green => [0, 255, 0],
+            Self::Magenta => [255, 0, 255],
+            Self::Red => [255, 0, 0],
+            Self::White => [255, 255, 255],
+            Self::Yellow => [255, 255, 0],
+        }
+    }
+
+    /// The `texttv.nu` HTML class of the colour, e.g. `bgB` for blue.
+    #[must_use]
+    pub const fn class(self) -> &'static str {
+        match self {
+            Self::Black => "bgBl",
+            Self::Blue => "bgB",
+            Self::Cyan => "bgC",
+            Self::Green => "bgG",
+            Self::Magenta => "bgM",
+            Self::Red => "bgR",
+            Self::White => "bgW",
+            Self::Yellow => "bgY",
+        }
+    }
+}
+
 impl FromStr for BgColour {
     type Err = Error;
 
@@ -97,7 +219,8 @@ impl FromStr for BgColour {
     }
 }
 
-#[derive(PartialEq, Eq, Debug, Default)]
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum FgColour {
     Black,
     Blue,
@@ -110,6 +233,38 @@ pub enum FgColour {
     Yellow,
 }
 
+impl FgColour {
+    /// The colour as RGB, as used by teletext.
+    #[must_use]
+    pub const fn rgb(self) -> [u8; 3] {
+        match self {
+            Self::Black => [0, 0, 0],
+            Self::Blue => [0, 0, 255],
+            Self::Cyan => [0, 255, 255],
+            Self::Green => [0, 255, 0],
+            Self::Magenta => [255, 0, 255],
+            Self::Red => [255, 0, 0],
+            Self::White => [255, 255, 255],
+            Self::Yellow => [255, 255, 0],
+        }
+    }
+
+    /// The `texttv.nu` HTML class of the colour, e.g. `Y` for yellow.
+    #[must_use]
+    pub const fn class(self) -> &'static str {
+        match self {
+            Self::Black => "bl",
+            Self::Blue => "B",
+            Self::Cyan => "C",
+            Self::Green => "G",
+            Self::Magenta => "M",
+            Self::Red => "R",
+            Self::White => "W",
+            Self::Yellow => "Y",
+        }
+    }
+}
+
 impl FromStr for FgColour {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -134,13 +289,244 @@ fn parse_gif_id(attr: &str) -> Option<u64> {
     attr[start..end].parse().ok()
 }
 
-/// Parse an HTML page from `texttv.nu/api` to a string that can be
-/// displayed in a terminal.
+/// Parse the image URL from a style attribute, e.g.
+/// `background-image: url('/images/1164105659.gif')`.
+fn parse_gif_url(attr: &str) -> Option<&str> {
+    let start = attr.find("url(")? + "url(".len();
+    let end = start + attr[start..].find(')')?;
+    Some(attr[start..end].trim_matches(|c| c == '\'' || c == '"'))
+}
+
+/// A mosaic GIF referenced by a page, that has not been decoded yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MosaicImage {
+    /// The ID of the image, from its file name.
+    pub id: u64,
+
+    /// The URL of the image, possibly relative to `https://texttv.nu`.
+    pub url: String,
+
+    /// The foreground colour the mosaic is drawn with.
+    pub fg: FgColour,
+
+    /// The background colour the mosaic is drawn on.
+    pub bg: BgColour,
+}
+
+/// Find the mosaic GIFs in `html` that cannot be mapped to a glyph by
+/// the lookup table, and have to be decoded. Each ID is listed once.
 ///
 /// # Errors
 ///
 /// Will return `Err` if `html` cannot be parsed.
-pub fn parse(html: &str) -> Result<Vec<Vec<Span>>, Error> {
+pub fn unknown_mosaics(html: &str) -> Result<Vec<MosaicImage>, Error> {
+    let fragment = Html::parse_fragment(html);
+    let Ok(selector) = Selector::parse(r#"span[class*="bgImg"]"#) else {
+        return Err(Error::ParseHtml("invalid texttv.nu HTML".into()));
+    };
+
+    let mut images: Vec<MosaicImage> = Vec::new();
+    for element in fragment.select(&selector) {
+        let (Some(class_attr), Some(style_attr)) = (element.attr("class"), element.attr("style"))
+        else {
+            continue;
+        };
+        let style = SpanStyle::from_str(class_attr)?;
+        let (Some(id), Some(url)) = (parse_gif_id(style_attr), parse_gif_url(style_attr)) else {
+            continue;
+        };
+        if mosaic::lookup(id).is_none() && images.iter().all(|image| image.id != id) {
+            images.push(MosaicImage {
+                id,
+                url: url.to_string(),
+                fg: style.fg,
+                bg: style.bg,
+            });
+        }
+    }
+    Ok(images)
+}
+
+/// The number of ASCII digits in `bytes` that end right before `end`.
+fn digits_before(bytes: &[u8], end: usize) -> usize {
+    bytes[..end]
+        .iter()
+        .rev()
+        .take_while(|b| b.is_ascii_digit())
+        .count()
+}
+
+/// Returns `true` if the digits at `bytes[start..end]` are part of a larger
+/// number, e.g. `12.345`, `1,250`, or `2 300` with a space as thousands
+/// separator, rather than a page number.
+///
+/// A space only separates thousands after a group of one or two digits that
+/// starts a word, e.g. `2 300` but not `SVT1 600`, or before a group of three digits that is not a page number, e.g.
+/// `300 000`, since lists of page numbers, e.g. `101 102`, are common.
+fn is_part_of_number(bytes: &[u8], start: usize, end: usize) -> bool {
+    let is_separator = |b: u8| matches!(b, b'.' | b',' | b':');
+    let before = start
+        .checked_sub(2)
+        .is_some_and(|i| is_separator(bytes[i + 1]) && bytes[i].is_ascii_digit());
+    let after =
+        end + 1 < bytes.len() && is_separator(bytes[end]) && bytes[end + 1].is_ascii_digit();
+
+    let space_before = start.checked_sub(1).is_some_and(|i| {
+        let digits = digits_before(bytes, i);
+        bytes[i] == b' '
+            && matches!(digits, 1 | 2)
+            && !(i - digits)
+                .checked_sub(1)
+                .is_some_and(|j| bytes[j].is_ascii_alphanumeric())
+    });
+    // A group of three digits starting with 0 is 000-099, never a page number.
+    let space_after = bytes.get(end) == Some(&b' ')
+        && bytes.get(end + 1) == Some(&b'0')
+        && digits_before(bytes, (end + 4).min(bytes.len())) == 3
+        && !bytes.get(end + 4).is_some_and(u8::is_ascii_digit);
+    before || after || space_before || space_after
+}
+
+/// Split `span` into spans of plain text and page numbers, e.g. `"mer på 377"`
+/// into `"mer på "` and `"377"`, where the latter links to page 377. Only
+/// numbers in `pages` are links.
+fn split_links(span: Span, pages: &RangeInclusive<u16>) -> Vec<Span> {
+    let bytes = span.content.as_bytes();
+    let mut spans = Vec::new();
+    let mut last = 0;
+    let mut start = 0;
+    while start < bytes.len() {
+        if !bytes[start].is_ascii_digit() {
+            start += 1;
+            continue;
+        }
+        let end = bytes[start..]
+            .iter()
+            .position(|b| !b.is_ascii_digit())
+            .map_or(bytes.len(), |n| start + n);
+        let target = span.content[start..end].parse::<u16>().ok();
+        if let Some(target) = target.filter(|nr| {
+            end - start == 3 && pages.contains(nr) && !is_part_of_number(bytes, start, end)
+        }) {
+            if last < start {
+                spans.push(Span {
+                    style: span.style.clone(),
+                    content: span.content[last..start].to_string(),
+                    link: None,
+                });
+            }
+            spans.push(Span {
+                style: span.style.clone(),
+                content: span.content[start..end].to_string(),
+                link: Some(target),
+            });
+            last = end;
+        }
+        start = end;
+    }
+
+    if last == 0 {
+        return vec![span];
+    }
+    if last < bytes.len() {
+        spans.push(Span {
+            content: span.content[last..].to_string(),
+            style: span.style,
+            link: None,
+        });
+    }
+    spans
+}
+
+impl Page {
+    fn from_lines(lines: Vec<Vec<Span>>, attrs: Vec<LineAttrs>) -> Self {
+        let mut found = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            for (j, span) in line.iter().enumerate() {
+                if let Some(target) = span.link {
+                    found.push(Link {
+                        line: i,
+                        span: j,
+                        target,
+                    });
+                }
+            }
+        }
+        Self {
+            lines,
+            attrs,
+            links: found,
+        }
+    }
+
+    /// The rows to display the page on. A double-height line takes up two
+    /// rows, and hides the line below it, like on a TV.
+    #[must_use]
+    pub fn display_rows(&self) -> Vec<DisplayRow> {
+        let mut rows = Vec::with_capacity(self.lines.len() + 1);
+        let mut i = 0;
+        while i < self.lines.len() {
+            if self.attrs.get(i).is_some_and(|attrs| attrs.double_height) {
+                rows.push(DisplayRow::Upper(i));
+                rows.push(DisplayRow::Lower(i));
+                i += 2;
+            } else {
+                rows.push(DisplayRow::Single(i));
+                i += 1;
+            }
+        }
+        rows
+    }
+
+    /// Draw all mosaics of the page with `glyphs`, including those of
+    /// double-height rows. Callers that draw rows in double height must not
+    /// call this, since the halves are taken from the sextants; they convert
+    /// each half with `mosaic::double_height` instead.
+    pub fn set_glyphs(&mut self, glyphs: Glyphs) {
+        if glyphs == Glyphs::Sextant {
+            return;
+        }
+        for span in self.lines.iter_mut().flatten() {
+            if span.style.mosaic {
+                span.content = span
+                    .content
+                    .chars()
+                    .map(|c| mosaic::convert(c, glyphs))
+                    .collect();
+            }
+        }
+    }
+
+    /// Create a page from plain text, with one line per row and the default
+    /// style. Numbers in `pages` are links.
+    #[must_use]
+    pub fn from_plain(text: &str, pages: &RangeInclusive<u16>) -> Self {
+        let lines = text
+            .lines()
+            .map(|line| {
+                split_links(
+                    Span {
+                        style: SpanStyle::default(),
+                        content: line.to_string(),
+                        link: None,
+                    },
+                    pages,
+                )
+            })
+            .collect::<Vec<_>>();
+        let attrs = vec![LineAttrs::default(); lines.len()];
+        Self::from_lines(lines, attrs)
+    }
+}
+
+/// Parse an HTML page from `texttv.nu/api` to a [`Page`] that can be
+/// displayed in a terminal. Page numbers in `pages` found in the text are
+/// split into separate spans, and collected as links.
+///
+/// # Errors
+///
+/// Will return `Err` if `html` cannot be parsed.
+pub fn parse(html: &str, pages: &RangeInclusive<u16>) -> Result<Page, Error> {
     let fragment = Html::parse_fragment(html);
 
     // Select `span` that represent a line of a page. These can be identified
@@ -157,42 +543,52 @@ pub fn parse(html: &str) -> Result<Vec<Vec<Span>>, Error> {
     } else {
         Vec::new()
     };
+    let mut attrs = Vec::with_capacity(page.capacity());
 
     for element in fragment.select(&selector) {
+        attrs.push(element.attr("class").unwrap_or_default().parse()?);
         let mut line = Vec::new();
         for c in element.child_elements() {
             let Some(class_attr) = c.attr("class") else {
                 return Err(Error::ParseHtml("no class string to parse".into()));
             };
 
-            let parsed_style = SpanStyle::from_str(class_attr)?;
+            let mut style = SpanStyle::from_str(class_attr)?;
 
             // If the HTML style references a GIF image, this means that a teletext mosaic
             // character should be picked to represent the GIF. Each mosaic has multiple
             // representations in the HTML-doc, one for each bg/fg colour combination that
-            // exists.
-            let text = if parsed_style.mosaic {
+            // exists. GIFs that have not been decoded yet are left blank.
+            let text = if style.mosaic {
                 let gif_id = c.attr("style").and_then(parse_gif_id).unwrap_or(0);
-                mosaic::from_gif_id(gif_id).to_string()
+                let glyph = mosaic::lookup(gif_id).unwrap_or(' ');
+                style.separated = mosaic::is_separated(glyph);
+                glyph.to_string()
             } else {
                 c.text().collect::<String>()
             };
 
             let span = Span {
                 content: text,
-                style: parsed_style,
+                style,
+                link: None,
             };
-            line.push(span);
+            if span.style.mosaic {
+                line.push(span);
+            } else {
+                line.extend(split_links(span, pages));
+            }
         }
         page.push(line);
     }
 
-    Ok(page)
+    Ok(Page::from_lines(page, attrs))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::texttv::PAGE_RANGE;
 
     struct TestCase {
         input: &'static str,
@@ -208,6 +604,7 @@ mod tests {
                     bg: BgColour::Blue,
                     fg: FgColour::White,
                     mosaic: false,
+                    separated: false,
                 },
             },
             TestCase {
@@ -216,6 +613,7 @@ mod tests {
                     bg: BgColour::Black,
                     fg: FgColour::White,
                     mosaic: false,
+                    separated: false,
                 },
             },
             TestCase {
@@ -224,6 +622,7 @@ mod tests {
                     fg: FgColour::White,
                     bg: BgColour::Blue,
                     mosaic: true,
+                    separated: false,
                 },
             },
         ];
@@ -232,4 +631,95 @@ mod tests {
             assert_eq!(result, case.expected);
         }
     }
+
+    #[test]
+    fn test_parse_links() {
+        let html =
+            r#"<span class="line"><span class="bgBl W">Inrikes 101 mer på 377-378</span></span>"#;
+        let page = parse(html, &PAGE_RANGE).unwrap();
+        let targets = page.links.iter().map(|l| l.target).collect::<Vec<_>>();
+        assert_eq!(targets, [101, 377, 378]);
+
+        let contents = page.lines[0]
+            .iter()
+            .map(|s| s.content.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, ["Inrikes ", "101", " mer på ", "377", "-", "378"]);
+        assert_eq!(
+            page.links[1],
+            Link {
+                line: 0,
+                span: 3,
+                target: 377
+            }
+        );
+    }
+
+    #[test]
+    fn test_double_height() {
+        let html = concat!(
+            r#"<span class="line toprow"><span class="bgBl W">100 SVT Text</span></span>"#,
+            r#"<span class="line DH"><span class="bgB Y">Nyheter</span></span>"#,
+            r#"<span class="line"><span class="bgB Y">Nyheter</span></span>"#,
+            r#"<span class="line"><span class="bgBl W">Inrikes 101</span></span>"#,
+        );
+        let page = parse(html, &PAGE_RANGE).unwrap();
+        assert!(page.attrs[0].top_row);
+        assert!(page.attrs[1].double_height);
+        assert_eq!(
+            page.display_rows(),
+            [
+                DisplayRow::Single(0),
+                DisplayRow::Upper(1),
+                DisplayRow::Lower(1),
+                DisplayRow::Single(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_mosaics() {
+        let html = concat!(
+            r#"<span class="line">"#,
+            r#"<span class="bgB Y bgImg" style="background-image: url('/images/42.gif')"> </span>"#,
+            r#"<span class="bgB Y bgImg" style="background-image: url('/images/42.gif')"> </span>"#,
+            r#"<span class="bgB W bgImg" style="background-image: url('/images/2308811616.gif')"> </span>"#,
+            "</span>"
+        );
+        let images = unknown_mosaics(html).unwrap();
+        assert_eq!(
+            images,
+            [MosaicImage {
+                id: 42,
+                url: "/images/42.gif".into(),
+                fg: FgColour::Yellow,
+                bg: BgColour::Blue,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_ignore_non_page_numbers() {
+        let page = Page::from_plain("Kl 12.345 år 2025, 1,250 kr och 050 eller 999", &PAGE_RANGE);
+        assert!(page.links.is_empty());
+        assert_eq!(page.lines[0].len(), 1);
+
+        let page = Page::from_plain("Pris 2 300 kr, 12 500 kr eller 300 000 kr", &PAGE_RANGE);
+        assert!(page.links.is_empty());
+        let page = Page::from_plain("Inrikes 101 102, sport 300", &PAGE_RANGE);
+        let targets = page.links.iter().map(|l| l.target).collect::<Vec<_>>();
+        assert_eq!(targets, [101, 102, 300]);
+
+        // Digits at the end of a word, e.g. a channel name, are not thousands.
+        let page = Page::from_plain("2 300 på SVT1 600, P4 710 och TV2 150", &PAGE_RANGE);
+        let targets = page.links.iter().map(|l| l.target).collect::<Vec<_>>();
+        assert_eq!(targets, [600, 710, 150]);
+    }
+
+    #[test]
+    fn test_links_within_page_range() {
+        let page = Page::from_plain("Sport 300, tv 850", &(100..=799));
+        let targets = page.links.iter().map(|l| l.target).collect::<Vec<_>>();
+        assert_eq!(targets, [300]);
+    }
 }
@@ -0,0 +1,269 @@
+use crate::diff;
+use crate::error::Error;
+use crate::mosaic::Glyphs;
+use crate::page::{self, BgColour, FgColour, Page, Span};
+use crate::texttv::{Breadcrumb, PageResponse};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+/// A fully parsed page, as written by `textty get --format json`.
+///
+/// Each page is written as a single JSON object on one line (JSON Lines):
+///
+/// ```json
+/// {
+///   "num": 377,
+///   "title": "SVT Text",
+///   "prev_page": 376,
+///   "next_page": 378,
+///   "updated_unix": 1700000000,
+///   "updated": "2023-11-14T22:13:20+00:00",
+///   "permalink": "https://texttv.nu/377/...",
+///   "breadcrumbs": [{ "name": "Sport", "url": "/300", "num": 300 }],
+///   "subpages": [
+///     [
+///       [{ "bg": "blue", "fg": "yellow", "mosaic": false, "separated": false, "text": "Resultat", "link": null }]
+///     ]
+///   ]
+/// }
+/// ```
+///
+/// `subpages` holds the page set; each subpage is a list of lines, and each
+/// line is a list of styled spans. Colours are one of `black`, `blue`, `cyan`,
+/// `green`, `magenta`, `red`, `white`, and `yellow`. Mosaic spans hold the
+/// block graphics glyph as `text`, and `separated` is set for separated
+/// mosaics. `link` is the page number referenced by
+/// the span, if any. Fields may be added, but are never removed or renamed.
+#[derive(Debug, Serialize)]
+pub struct JsonPage<'a> {
+    pub num: u16,
+    pub title: &'a str,
+    pub prev_page: u16,
+    pub next_page: u16,
+    pub updated_unix: i64,
+    /// The update time in RFC 3339 format, in UTC, or `null` if unknown.
+    pub updated: Option<String>,
+    pub permalink: &'a str,
+    pub breadcrumbs: &'a [Breadcrumb],
+    pub subpages: Vec<Vec<Vec<Span>>>,
+}
+
+impl<'a> JsonPage<'a> {
+    /// Parse the page set of `response`, with links to the page numbers in
+    /// `pages` and mosaics drawn with `glyphs`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the content of the page cannot be parsed.
+    pub fn new(
+        response: &'a PageResponse,
+        pages: &RangeInclusive<u16>,
+        glyphs: Glyphs,
+    ) -> Result<Self, Error> {
+        let subpages = response
+            .content
+            .iter()
+            .map(|content| {
+                let mut page = page::parse(content, pages)?;
+                page.set_glyphs(glyphs);
+                Ok(page.lines)
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Self {
+            num: response.num,
+            title: &response.title,
+            prev_page: response.prev_page,
+            next_page: response.next_page,
+            updated_unix: response.date_updated_unix,
+            updated: response.updated().map(|dt| dt.to_rfc3339()),
+            permalink: &response.permalink,
+            breadcrumbs: &response.breadcrumbs,
+            subpages,
+        })
+    }
+}
+
+/// The ANSI SGR code for a foreground colour.
+const fn fg_code(fg: FgColour) -> u8 {
+    match fg {
+        FgColour::Black => 30,
+        FgColour::Red => 31,
+        FgColour::Green => 32,
+        FgColour::Yellow => 33,
+        FgColour::Blue => 34,
+        FgColour::Magenta => 35,
+        FgColour::Cyan => 36,
+        FgColour::White => 37,
+    }
+}
+
+/// The ANSI SGR code for a background colour.
+const fn bg_code(bg: BgColour) -> u8 {
+    match bg {
+        BgColour::Black => 40,
+        BgColour::Red => 41,
+        BgColour::Green => 42,
+        BgColour::Yellow => 43,
+        BgColour::Blue => 44,
+        BgColour::Magenta => 45,
+        BgColour::Cyan => 46,
+        BgColour::White => 47,
+    }
+}
+
+/// Write `page` to `w` with ANSI colour escape codes, one line per row.
+///
+/// # Errors
+///
+/// Will return `Err` if writing to `w` fails.
+pub fn write_ansi(w: &mut impl Write, page: &Page) -> io::Result<()> {
+    for line in &page.lines {
+        for span in line {
+            write!(
+                w,
+                "\x1b[{};{}m{}",
+                fg_code(span.style.fg),
+                bg_code(span.style.bg),
+                span.content
+            )?;
+        }
+        writeln!(w, "\x1b[0m")?;
+    }
+    Ok(())
+}
+
+/// Write `page` to `w` as plain text, one line per row.
+///
+/// # Errors
+///
+/// Will return `Err` if writing to `w` fails.
+pub fn write_plain(w: &mut impl Write, page: &Page) -> io::Result<()> {
+    for line in &page.lines {
+        for span in line {
+            w.write_all(span.content.as_bytes())?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// Write the lines that differ between the page sets `old` and `new` to `w`,
+/// with the old line prefixed by `-` and the new by `+`, and the row number,
+/// counted from the header row at 0. With `colour`, old lines are red and new
+/// lines green. If the page set has more than one page, each page with changes
+/// is headed by its position in the set. Returns the number of changed lines.
+///
+/// # Errors
+///
+/// Will return `Err` if writing to `w` fails.
+pub fn write_diff(
+    w: &mut impl Write,
+    old: &[Page],
+    new: &[Page],
+    colour: bool,
+) -> io::Result<usize> {
+    let (red, green, reset) = if colour {
+        ("\x1b[31m", "\x1b[32m", "\x1b[0m")
+    } else {
+        ("", "", "")
+    };
+    let mut count = 0;
+    for (i, changed) in diff::changed_page_set(old, new).into_iter().enumerate() {
+        if changed.is_empty() {
+            continue;
+        }
+        if new.len() > 1 {
+            writeln!(w, "@@ {}/{} @@", i + 1, new.len())?;
+        }
+        for row in changed {
+            if let Some(line) = old.get(i).and_then(|page| page.lines.get(row)) {
+                writeln!(w, "{red}-{row:>2} {}{reset}", diff::line_text(line))?;
+            }
+            if let Some(line) = new.get(i).and_then(|page| page.lines.get(row)) {
+                writeln!(w, "{green}+{row:>2} {}{reset}", diff::line_text(line))?;
+            }
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Write `response` to `w` as a [`JsonPage`] on a single line.
+///
+/// # Errors
+///
+/// Will return `Err` if the page cannot be parsed, or if writing to `w` fails.
+pub fn write_json(
+    w: &mut impl Write,
+    response: &PageResponse,
+    pages: &RangeInclusive<u16>,
+    glyphs: Glyphs,
+) -> Result<(), Error> {
+    serde_json::to_writer(&mut *w, &JsonPage::new(response, pages, glyphs)?)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texttv::PAGE_RANGE;
+
+    const HTML: &str = r#"<span class="line"><span class="bgB Y">Sport</span><span class="bgBl W"> 300</span></span>"#;
+
+    #[test]
+    fn test_write_diff() {
+        let old = crate::page::parse(HTML, &PAGE_RANGE).unwrap();
+        let new = crate::page::parse(&HTML.replace("300", "301"), &PAGE_RANGE).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(write_diff(&mut out, &[old], &[new], false).unwrap(), 1);
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, "- 0 Sport 300\n+ 0 Sport 301\n");
+    }
+
+    #[test]
+    fn test_write_ansi() {
+        let page = crate::page::parse(HTML, &PAGE_RANGE).unwrap();
+        let mut out = Vec::new();
+        write_ansi(&mut out, &page).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, "\x1b[33;44mSport\x1b[37;40m \x1b[37;40m300\x1b[0m\n");
+    }
+
+    #[test]
+    fn test_write_json() {
+        let response = PageResponse {
+            num: 300,
+            title: "Sport".into(),
+            content: vec![HTML.into()],
+            content_plain: None,
+            next_page: 301,
+            prev_page: 299,
+            date_updated_unix: 1_700_000_000,
+            permalink: "https://texttv.nu/300/a".into(),
+            id: 1,
+            breadcrumbs: Vec::new(),
+        };
+        let mut out = Vec::new();
+        write_json(&mut out, &response, &PAGE_RANGE, Glyphs::Sextant).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["num"], 300);
+        assert_eq!(json["updated"], "2023-11-14T22:13:20+00:00");
+        assert_eq!(
+            json["subpages"][0][0][0],
+            serde_json::json!({
+                "bg": "blue", "fg": "yellow", "mosaic": false, "separated": false, "text": "Sport", "link": null
+            })
+        );
+        assert_eq!(json["subpages"][0][0][2]["link"], 300);
+    }
+
+    #[test]
+    fn test_write_plain() {
+        let page = crate::page::parse(HTML, &PAGE_RANGE).unwrap();
+        let mut out = Vec::new();
+        write_plain(&mut out, &page).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "Sport 300\n");
+    }
+}
@@ -0,0 +1,460 @@
+use crate::cache::Cache;
+use crate::error::Error;
+use crate::nordic::{NordicClient, Service};
+use crate::svt::SvtClient;
+use crate::texttv::{self, ClientBuilder, PageNumber, PageResponse, PageUpdate, Validators};
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+/// A source of pages, e.g. the `texttv.nu` API, a directory of saved pages,
+/// or pages kept in memory.
+pub trait PageSource {
+    /// Get page `number`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the page does not exist in the source, or if it
+    /// cannot be read.
+    fn get_page(&self, number: u16) -> Result<PageResponse, Error>;
+
+    /// Get page `number`, like [`PageSource::get_page`], along with the
+    /// validators of the response. By default, there are no validators.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the page does not exist in the source, or if it
+    /// cannot be read.
+    fn get_page_with_validators(&self, number: u16) -> Result<(PageResponse, Validators), Error> {
+        Ok((self.get_page(number)?, Validators::default()))
+    }
+
+    /// Get the pages from `lo` to `hi`. Pages missing from the source are
+    /// skipped.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `lo` is greater than `hi`, or if a page cannot be
+    /// read.
+    fn get_page_range(&self, lo: u16, hi: u16) -> Result<Vec<PageResponse>, Error> {
+        if hi < lo {
+            return Err(Error::InvalidPageRange { lo, hi });
+        }
+        let mut pages = Vec::new();
+        for number in lo..=hi {
+            match self.get_page(number) {
+                Ok(page) => pages.push(page),
+                Err(Error::InvalidPageNumber(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(pages)
+    }
+
+    /// Get page `known.num` if it has changed since `known` was fetched, with
+    /// `validators` from the earlier response. By default, the page is
+    /// fetched and compared with `known`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the page does not exist in the source, or if it
+    /// cannot be read.
+    fn get_page_if_changed(
+        &self,
+        known: &PageResponse,
+        _validators: &Validators,
+    ) -> Result<PageUpdate, Error> {
+        let page = self.get_page(known.num)?;
+        if page.is_same_state(known) {
+            Ok(PageUpdate::Unchanged)
+        } else {
+            Ok(PageUpdate::Changed {
+                page,
+                validators: Validators::default(),
+            })
+        }
+    }
+
+    /// The previous available page, before `page`. By default, the previous
+    /// page given by `page` itself.
+    fn prev_page(&self, page: &PageResponse) -> u16 {
+        page.prev_page
+    }
+
+    /// The next available page, after `page`. By default, the next page given
+    /// by `page` itself.
+    fn next_page(&self, page: &PageResponse) -> u16 {
+        page.next_page
+    }
+
+    /// The range of page numbers of the source, that page links point into.
+    /// By default, the page range of the `texttv.nu` API.
+    fn page_range(&self) -> RangeInclusive<u16> {
+        texttv::PAGE_RANGE
+    }
+
+    /// Get an image referenced by a page, e.g. a mosaic GIF.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the image cannot be fetched; by default, sources
+    /// do not support images.
+    fn get_image(&self, _url: &str) -> Result<Vec<u8>, Error> {
+        Err(Error::Unsupported("images"))
+    }
+}
+
+impl<S: PageSource + ?Sized> PageSource for Box<S> {
+    fn get_page(&self, number: u16) -> Result<PageResponse, Error> {
+        (**self).get_page(number)
+    }
+
+    fn get_page_with_validators(&self, number: u16) -> Result<(PageResponse, Validators), Error> {
+        (**self).get_page_with_validators(number)
+    }
+
+    fn get_page_range(&self, lo: u16, hi: u16) -> Result<Vec<PageResponse>, Error> {
+        (**self).get_page_range(lo, hi)
+    }
+
+    fn get_page_if_changed(
+        &self,
+        known: &PageResponse,
+        validators: &Validators,
+    ) -> Result<PageUpdate, Error> {
+        (**self).get_page_if_changed(known, validators)
+    }
+
+    fn prev_page(&self, page: &PageResponse) -> u16 {
+        (**self).prev_page(page)
+    }
+
+    fn next_page(&self, page: &PageResponse) -> u16 {
+        (**self).next_page(page)
+    }
+
+    fn page_range(&self) -> RangeInclusive<u16> {
+        (**self).page_range()
+    }
+
+    fn get_image(&self, url: &str) -> Result<Vec<u8>, Error> {
+        (**self).get_image(url)
+    }
+}
+
+/// The service that pages are read from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// SVT Text-TV from the `texttv.nu` API, with colours and block graphics.
+    #[default]
+    Texttv,
+    /// SVT Text-TV from SVT's own API at `svt.se/text-tv`, with plain text only.
+    Svt,
+    /// Finnish YLE Teksti-TV.
+    Yle,
+    /// Danish DR Tekst-TV.
+    Dr,
+    /// Norwegian NRK Tekst-TV.
+    Nrk,
+}
+
+impl Backend {
+    /// The name of the directory that pages from the backend are cached in.
+    #[must_use]
+    pub const fn cache_name(self) -> &'static str {
+        match self {
+            Self::Texttv => "pages",
+            Self::Svt => "pages-svt",
+            Self::Yle => "pages-yle",
+            Self::Dr => "pages-dr",
+            Self::Nrk => "pages-nrk",
+        }
+    }
+
+    /// The Nordic service of the backend, if it is not SVT.
+    #[must_use]
+    pub const fn service(self) -> Option<Service> {
+        match self {
+            Self::Texttv | Self::Svt => None,
+            Self::Yle => Some(Service::Yle),
+            Self::Dr => Some(Service::Dr),
+            Self::Nrk => Some(Service::Nrk),
+        }
+    }
+
+    /// The range of page numbers of the backend.
+    #[must_use]
+    pub const fn page_range(self) -> RangeInclusive<u16> {
+        match self.service() {
+            Some(service) => service.page_range(),
+            None => texttv::MIN_PAGE_NR..=texttv::MAX_PAGE_NR,
+        }
+    }
+
+    /// The first page of the backend.
+    #[must_use]
+    pub const fn home_page(self) -> u16 {
+        match self.service() {
+            Some(service) => service.home_page(),
+            None => texttv::HOME_PAGE_NR,
+        }
+    }
+}
+
+/// The page source of `backend`, with the HTTP settings of `settings`.
+///
+/// # Errors
+///
+/// Will return `Err` if the proxy URL of `settings` is invalid.
+pub fn for_backend(
+    backend: Backend,
+    settings: &ClientBuilder,
+) -> Result<Box<dyn PageSource + Send>, Error> {
+    if let Some(service) = backend.service() {
+        return Ok(Box::new(NordicClient::new(service, settings)?));
+    }
+    match backend {
+        Backend::Svt => Ok(Box::new(SvtClient::with_settings(settings)?)),
+        _ => Ok(Box::new(settings.clone().build()?)),
+    }
+}
+
+impl PageSource for texttv::Client {
+    fn get_page(&self, number: u16) -> Result<PageResponse, Error> {
+        self.get_page(PageNumber::from(number))
+    }
+
+    fn get_page_with_validators(&self, number: u16) -> Result<(PageResponse, Validators), Error> {
+        self.get_page_with_validators(PageNumber::from(number))
+    }
+
+    fn get_page_range(&self, lo: u16, hi: u16) -> Result<Vec<PageResponse>, Error> {
+        self.get_page_range(PageNumber::from(lo), PageNumber::from(hi))
+    }
+
+    fn get_page_if_changed(
+        &self,
+        known: &PageResponse,
+        validators: &Validators,
+    ) -> Result<PageUpdate, Error> {
+        self.get_page_if_changed(known, validators)
+    }
+
+    fn get_image(&self, url: &str) -> Result<Vec<u8>, Error> {
+        self.get_image(url)
+    }
+}
+
+impl PageSource for Cache {
+    fn get_page(&self, number: u16) -> Result<PageResponse, Error> {
+        match self.load(number)? {
+            Some(cached) => Ok(cached.page),
+            None => Err(Error::InvalidPageNumber(number)),
+        }
+    }
+}
+
+/// The available page before `number` among `numbers`, wrapping around to the
+/// last page; `number` itself if there is no other page.
+fn prev_of(numbers: impl DoubleEndedIterator<Item = u16> + Clone, number: u16) -> u16 {
+    numbers
+        .clone()
+        .rev()
+        .find(|&n| n < number)
+        .or_else(|| numbers.last())
+        .unwrap_or(number)
+}
+
+/// The available page after `number` among `numbers`, wrapping around to the
+/// first page; `number` itself if there is no other page.
+fn next_of(mut numbers: impl Iterator<Item = u16> + Clone, number: u16) -> u16 {
+    numbers
+        .clone()
+        .find(|&n| n > number)
+        .or_else(|| numbers.next())
+        .unwrap_or(number)
+}
+
+/// Pages saved as JSON files in a directory, named by page number, e.g.
+/// `377.json`. A file holds a page as returned by the `texttv.nu` API, either
+/// as a single object or as an array with one page.
+///
+/// Previous and next pages are the neighbouring files in the directory, as
+/// indexed when the source is created.
+#[derive(Debug, Clone)]
+pub struct FsSource {
+    dir: PathBuf,
+    /// The page numbers of the files in the directory, in order.
+    numbers: Vec<u16>,
+}
+
+impl FsSource {
+    /// Read pages from the files in `dir`. If the directory cannot be
+    /// indexed, the previous and next pages are the ones given by the pages.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        let numbers = Self::index(&dir).unwrap_or_else(|err| {
+            log::warn!("could not index {}: {err}", dir.display());
+            Vec::new()
+        });
+        Self { dir, numbers }
+    }
+
+    /// The page numbers of the files in `dir`, in order.
+    fn index(dir: &Path) -> Result<Vec<u16>, Error> {
+        let mut numbers = fs::read_dir(dir)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                path.file_stem()?.to_str()?.parse().ok()
+            })
+            .collect::<Vec<u16>>();
+        numbers.sort_unstable();
+        Ok(numbers)
+    }
+}
+
+impl PageSource for FsSource {
+    fn get_page(&self, number: u16) -> Result<PageResponse, Error> {
+        let bytes = match fs::read(self.dir.join(format!("{number}.json"))) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Err(Error::InvalidPageNumber(number));
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if bytes.trim_ascii_start().starts_with(b"[") {
+            let mut pages: Vec<PageResponse> = serde_json::from_slice(&bytes)?;
+            pages.pop().ok_or(Error::InvalidPageNumber(number))
+        } else {
+            Ok(serde_json::from_slice(&bytes)?)
+        }
+    }
+
+    fn prev_page(&self, page: &PageResponse) -> u16 {
+        if self.numbers.is_empty() {
+            return page.prev_page;
+        }
+        prev_of(self.numbers.iter().copied(), page.num)
+    }
+
+    fn next_page(&self, page: &PageResponse) -> u16 {
+        if self.numbers.is_empty() {
+            return page.next_page;
+        }
+        next_of(self.numbers.iter().copied(), page.num)
+    }
+}
+
+/// Pages kept in memory, e.g. for tests.
+///
+/// Previous and next pages are the neighbouring pages in the source.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    pages: BTreeMap<u16, PageResponse>,
+}
+
+impl MemorySource {
+    /// Create an empty source.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `page`, replacing any page with the same number.
+    pub fn insert(&mut self, page: PageResponse) {
+        self.pages.insert(page.num, page);
+    }
+}
+
+impl FromIterator<PageResponse> for MemorySource {
+    fn from_iter<I: IntoIterator<Item = PageResponse>>(iter: I) -> Self {
+        let mut source = Self::new();
+        for page in iter {
+            source.insert(page);
+        }
+        source
+    }
+}
+
+impl PageSource for MemorySource {
+    fn get_page(&self, number: u16) -> Result<PageResponse, Error> {
+        self.pages
+            .get(&number)
+            .cloned()
+            .ok_or(Error::InvalidPageNumber(number))
+    }
+
+    fn get_page_range(&self, lo: u16, hi: u16) -> Result<Vec<PageResponse>, Error> {
+        if hi < lo {
+            return Err(Error::InvalidPageRange { lo, hi });
+        }
+        Ok(self
+            .pages
+            .range(lo..=hi)
+            .map(|(_, page)| page.clone())
+            .collect())
+    }
+
+    fn prev_page(&self, page: &PageResponse) -> u16 {
+        prev_of(self.pages.keys().copied(), page.num)
+    }
+
+    fn next_page(&self, page: &PageResponse) -> u16 {
+        next_of(self.pages.keys().copied(), page.num)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texttv::test_page;
+
+    #[test]
+    fn test_memory_source() {
+        let source = [100, 377, 401]
+            .into_iter()
+            .map(test_page)
+            .collect::<MemorySource>();
+        assert_eq!(source.get_page(377).unwrap().title, "Sida 377");
+        assert!(matches!(
+            source.get_page(378),
+            Err(Error::InvalidPageNumber(378))
+        ));
+        let range = source.get_page_range(300, 450).unwrap();
+        assert_eq!(range.iter().map(|p| p.num).collect::<Vec<_>>(), [377, 401]);
+        assert_eq!(source.next_page(&test_page(377)), 401);
+        assert_eq!(source.next_page(&test_page(401)), 100);
+        assert_eq!(source.prev_page(&test_page(100)), 401);
+    }
+
+    #[test]
+    fn test_fs_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let single = serde_json::to_vec(&test_page(377)).unwrap();
+        let array = serde_json::to_vec(&[test_page(100)]).unwrap();
+        fs::write(dir.path().join("377.json"), single).unwrap();
+        fs::write(dir.path().join("100.json"), array).unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        let source = FsSource::new(dir.path());
+        assert_eq!(source.get_page(377).unwrap().num, 377);
+        assert_eq!(source.get_page(100).unwrap().num, 100);
+        assert!(matches!(
+            source.get_page(200),
+            Err(Error::InvalidPageNumber(200))
+        ));
+        assert_eq!(source.next_page(&test_page(100)), 377);
+        assert_eq!(source.prev_page(&test_page(100)), 377);
+        assert_eq!(source.get_page_range(100, 399).unwrap().len(), 2);
+
+        // The directory is indexed once, when the source is created.
+        fs::write(dir.path().join("200.json"), "{}").unwrap();
+        assert_eq!(source.next_page(&test_page(100)), 377);
+    }
+}
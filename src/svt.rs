@@ -0,0 +1,216 @@
+//! A client for SVT's own Text-TV API at `svt.se/text-tv`, as an alternative
+//! to the `texttv.nu` API.
+//!
+//! SVT only provides the page text, without colours, so pages are normalised
+//! into a [`PageResponse`] with plain white-on-black HTML content, that can be
+//! turned into a [`crate::model::Page`] like any other page.
+
+use crate::error::Error;
+use crate::source::PageSource;
+use crate::texttv::{ClientBuilder, Http, MAX_PAGE_NR, MIN_PAGE_NR, PageResponse};
+use chrono::DateTime;
+use serde::Deserialize;
+use serde_aux::field_attributes::deserialize_number_from_string;
+
+const BASE_URL: &str = "https://www.svt.se/text-tv";
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// The response body of `svt.se/text-tv/api/<page>`.
+#[derive(Debug, Deserialize)]
+struct SvtResponse {
+    data: SvtPage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SvtPage {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    page_number: u16,
+
+    /// The previous page; empty if there is none.
+    #[serde(default)]
+    prev_page: String,
+
+    /// The next page; empty if there is none.
+    #[serde(default)]
+    next_page: String,
+
+    #[serde(default)]
+    meta: Option<SvtMeta>,
+
+    sub_pages: Vec<SvtSubPage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SvtMeta {
+    /// The update time in RFC 3339 format.
+    updated: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SvtSubPage {
+    /// The page text, with one line per row.
+    alt_text: String,
+}
+
+/// Escape `text` for use as HTML content.
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// An ID for a page state, for the backends whose APIs have none, derived
+/// from the text of its subpages with 64-bit FNV-1a. Unlike `DefaultHasher`, the ID is the same in every build, so IDs
+/// in the cache stay valid after an upgrade.
+pub(crate) fn content_id<S: AsRef<str>>(texts: &[S]) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS;
+    for text in texts {
+        // 0xff never occurs in UTF-8, so it separates the subpages.
+        for &b in text.as_ref().as_bytes().iter().chain(&[0xff]) {
+            hash ^= u64::from(b);
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
+/// Lay out plain `text` as `texttv.nu` HTML, with one white-on-black line
+/// per row.
+fn to_html(text: &str) -> String {
+    let mut html = String::with_capacity(2 * text.len());
+    for line in text.lines() {
+        html.push_str(r#"<span class="line"><span class="bgBl W">"#);
+        html.push_str(&escape_html(line));
+        html.push_str("</span></span>");
+    }
+    html
+}
+
+/// Parse a neighbouring page number, falling back to `default` if it is
+/// missing or out of range.
+fn parse_neighbour(page: &str, default: u16) -> u16 {
+    page.parse()
+        .ok()
+        .filter(|nr| (MIN_PAGE_NR..=MAX_PAGE_NR).contains(nr))
+        .unwrap_or(default)
+}
+
+impl From<SvtPage> for PageResponse {
+    fn from(page: SvtPage) -> Self {
+        let num = page.page_number;
+        let content_plain = page
+            .sub_pages
+            .into_iter()
+            .map(|sub_page| sub_page.alt_text)
+            .collect::<Vec<_>>();
+        let date_updated_unix = page
+            .meta
+            .and_then(|meta| meta.updated)
+            .and_then(|updated| DateTime::parse_from_rfc3339(&updated).ok())
+            .map_or(0, |dt| dt.timestamp());
+        Self {
+            num,
+            title: format!("SVT Text {num}"),
+            content: content_plain.iter().map(|text| to_html(text)).collect(),
+            next_page: parse_neighbour(&page.next_page, num),
+            prev_page: parse_neighbour(&page.prev_page, num),
+            date_updated_unix,
+            permalink: format!("{BASE_URL}/{num}"),
+            id: content_id(&content_plain),
+            breadcrumbs: Vec::new(),
+            content_plain: Some(content_plain),
+        }
+    }
+}
+
+/// Parse a response body from `svt.se/text-tv/api/<page>`.
+///
+/// # Errors
+///
+/// Will return `Err` if `json` is not a valid response.
+pub fn parse_response(json: &[u8]) -> Result<PageResponse, Error> {
+    let response: SvtResponse = serde_json::from_slice(json)?;
+    Ok(response.data.into())
+}
+
+/// A client for SVT's Text-TV API.
+#[derive(Debug)]
+pub struct SvtClient {
+    http: Http,
+}
+
+impl SvtClient {
+    /// Create a client with the HTTP settings of `settings`, as described on
+    /// [`ClientBuilder`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the proxy URL is invalid.
+    pub fn with_settings(settings: &ClientBuilder) -> Result<Self, Error> {
+        Ok(Self {
+            http: settings.http()?,
+        })
+    }
+}
+
+impl Default for SvtClient {
+    fn default() -> Self {
+        Self::with_settings(&ClientBuilder::default())
+            .expect("the default settings have no proxy to fail on")
+    }
+}
+
+impl PageSource for SvtClient {
+    fn get_page(&self, number: u16) -> Result<PageResponse, Error> {
+        let number = number.clamp(MIN_PAGE_NR, MAX_PAGE_NR);
+        let url = format!("{BASE_URL}/api/{number}");
+        let body = self
+            .http
+            .send(|agent| agent.get(&url).call()?.body_mut().read_to_vec())?;
+        parse_response(&body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page;
+    use crate::texttv::PAGE_RANGE;
+
+    const FIXTURE: &[u8] = include_bytes!("../tests/fixtures/svt/377.json");
+
+    #[test]
+    fn test_parse_response() {
+        let response = parse_response(FIXTURE).unwrap();
+        assert_eq!(response.num, 377);
+        assert_eq!((response.prev_page, response.next_page), (376, 378));
+        assert_eq!(response.date_updated_unix, 1_710_006_120);
+        assert_eq!(response.content.len(), 2);
+        assert_eq!(response.permalink, "https://www.svt.se/text-tv/377");
+
+        let page = page::parse(&response.content[0], &PAGE_RANGE).unwrap();
+        let text = page.lines[7]
+            .iter()
+            .map(|span| span.content.as_str())
+            .collect::<String>();
+        assert_eq!(text, " Fotboll & <mer> på 300");
+        let targets = page.links.iter().map(|l| l.target).collect::<Vec<_>>();
+        assert_eq!(targets, [377, 378, 379, 300]);
+    }
+
+    #[test]
+    fn test_content_id() {
+        assert_eq!(content_id(&["Sport"]), 0x8a11_efd2_5532_50f6);
+        assert_ne!(content_id(&["ab", "c"]), content_id(&["a", "bc"]));
+    }
+}
@@ -1,19 +1,34 @@
 #![allow(dead_code)]
 
 use crate::error::Error;
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use serde_aux::field_attributes::deserialize_number_from_string;
 use std::cmp::PartialOrd;
 use std::fmt::{self, Display, Formatter};
+use std::hash::{BuildHasher, RandomState};
+use std::ops::RangeInclusive;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
 
 const BASE_URL: &str = "https://texttv.nu/api";
 const APP_ID: &str = "textty";
+const USER_AGENT: &str = concat!("textty/", env!("CARGO_PKG_VERSION"));
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+const RETRIES: u32 = 2;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
 
 pub const HOME_PAGE_NR: u16 = 100;
 pub const MIN_PAGE_NR: u16 = 100;
 pub const MAX_PAGE_NR: u16 = 899;
+/// The page numbers of SVT Text-TV.
+pub const PAGE_RANGE: RangeInclusive<u16> = MIN_PAGE_NR..=MAX_PAGE_NR;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PageResponse {
     /// The page number.
     #[serde(deserialize_with = "deserialize_number_from_string")]
@@ -37,7 +52,8 @@ pub struct PageResponse {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub prev_page: u16,
 
-    /// A UNIX timestamp representing the time the page was last updated.
+    /// A UNIX timestamp representing the time the page was last updated, or
+    /// 0 if the service does not tell.
     pub date_updated_unix: i64,
 
     /// A permanent link to the current page state.
@@ -53,7 +69,65 @@ pub struct PageResponse {
     pub breadcrumbs: Vec<Breadcrumb>,
 }
 
-#[derive(Debug, Deserialize)]
+impl PageResponse {
+    /// The time the page was last updated, if known.
+    #[must_use]
+    pub fn updated(&self) -> Option<DateTime<Utc>> {
+        if self.date_updated_unix <= 0 {
+            return None;
+        }
+        DateTime::from_timestamp(self.date_updated_unix, 0)
+    }
+
+    /// Returns `true` if `other` is the same page state, i.e. it has the same
+    /// ID and update time.
+    #[must_use]
+    pub fn is_same_state(&self, other: &Self) -> bool {
+        self.id == other.id && self.date_updated_unix == other.date_updated_unix
+    }
+}
+
+/// A page `num` for tests, with an empty page and the page number as its ID.
+#[cfg(test)]
+pub(crate) fn test_page(num: u16) -> PageResponse {
+    PageResponse {
+        num,
+        title: format!("Sida {num}"),
+        content: vec![String::new()],
+        content_plain: None,
+        next_page: num + 1,
+        prev_page: num - 1,
+        date_updated_unix: 1_700_000_000,
+        permalink: String::new(),
+        id: u64::from(num),
+        breadcrumbs: Vec::new(),
+    }
+}
+
+/// The HTTP validators of a fetched page, if the server sends them, used to
+/// ask for the page again only if it has changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Validators {
+    /// The `ETag` header of the response.
+    pub etag: Option<String>,
+
+    /// The `Last-Modified` header of the response.
+    pub last_modified: Option<String>,
+}
+
+/// The result of asking for a page only if it has changed.
+#[derive(Debug, Clone)]
+pub enum PageUpdate {
+    /// The page has a new state.
+    Changed {
+        page: PageResponse,
+        validators: Validators,
+    },
+    /// The page is the same as the known page.
+    Unchanged,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Breadcrumb {
     /// The name of the page.
     pub name: String,
@@ -89,18 +163,277 @@ impl From<u16> for PageNumber {
 
 #[derive(Debug)]
 pub struct Client {
-    inner: ureq::Agent,
+    http: Http,
+    base_url: String,
+    app_id: String,
+}
+
+/// An HTTP agent with the settings of a [`ClientBuilder`], that sends
+/// requests rate limited and retries them after transient errors.
+#[derive(Debug)]
+pub(crate) struct Http {
+    agent: ureq::Agent,
+    retries: u32,
+    retry_delay: Duration,
+    limiter: RateLimiter,
+}
+
+impl Http {
+    /// Send a request with `send`, rate limited, and retried with backoff
+    /// after transient errors.
+    pub(crate) fn send<T>(
+        &self,
+        send: impl Fn(&ureq::Agent) -> Result<T, ureq::Error>,
+    ) -> Result<T, ureq::Error> {
+        let mut retry = 0;
+        loop {
+            self.limiter.wait();
+            match send(&self.agent) {
+                Err(err) if retry < self.retries && is_transient(&err) => {
+                    let delay = backoff(self.retry_delay, retry);
+                    log::info!("retrying in {delay:?} after error: {err}");
+                    thread::sleep(delay);
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Spaces out requests by at least a minimum interval, across all threads
+/// using the client.
+#[derive(Debug)]
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Wait until the next request may be sent.
+    fn wait(&self) {
+        if self.interval.is_zero() {
+            return;
+        }
+        // Hold the lock while sleeping, so that waiting requests go in turn.
+        let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        if let Some(wait) = next.and_then(|at| at.checked_duration_since(now)) {
+            thread::sleep(wait);
+        }
+        *next = Some(Instant::now() + self.interval);
+    }
+}
+
+/// An error that may go away if the request is sent again: a timeout, a lost
+/// connection, a server error, or too many requests.
+fn is_transient(err: &ureq::Error) -> bool {
+    match err {
+        ureq::Error::StatusCode(status) => *status == 429 || *status >= 500,
+        ureq::Error::Io(_)
+        | ureq::Error::Timeout(_)
+        | ureq::Error::ConnectionFailed
+        | ureq::Error::BodyStalled => true,
+        _ => false,
+    }
+}
+
+/// The delay before retry number `retry`, counting from zero: `delay` doubled
+/// for each retry, up to a maximum, with a random jitter of up to half of it.
+fn backoff(delay: Duration, retry: u32) -> Duration {
+    let delay = delay
+        .saturating_mul(2u32.saturating_pow(retry))
+        .min(MAX_RETRY_DELAY);
+    // The hashers of a new `RandomState` are randomly seeded.
+    let random = RandomState::new().hash_one(retry);
+    #[allow(clippy::cast_precision_loss)]
+    let jitter = (random % 1000) as f64 / 1000.0;
+    delay.mul_f64(1.0 - jitter / 2.0)
 }
 
 impl Default for Client {
     fn default() -> Self {
-        let agent = ureq::agent();
-        Self { inner: agent }
+        ClientBuilder::default()
+            .build()
+            .expect("the default client has no proxy to fail on")
+    }
+}
+
+/// A builder for a [`Client`] with custom HTTP settings.
+///
+/// The same settings are used by the clients of the other backends, e.g.
+/// [`crate::svt::SvtClient`]: requests are rate limited and retried in the
+/// same way, but the base URL and the app ID only apply to the `texttv.nu`
+/// API. Each client has a rate limit of its own.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use textty::texttv::Client;
+///
+/// let client = Client::builder()
+///     .proxy("http://proxy.example.com:8080")
+///     .connect_timeout(Duration::from_secs(5))
+///     .build()?;
+/// # Ok::<(), textty::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    base_url: String,
+    app_id: String,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    proxy: Option<String>,
+    user_agent: String,
+    retries: u32,
+    retry_delay: Duration,
+    min_interval: Duration,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self {
+            base_url: BASE_URL.to_string(),
+            app_id: APP_ID.to_string(),
+            connect_timeout: Some(CONNECT_TIMEOUT),
+            read_timeout: Some(READ_TIMEOUT),
+            proxy: None,
+            user_agent: USER_AGENT.to_string(),
+            retries: RETRIES,
+            retry_delay: RETRY_DELAY,
+            min_interval: MIN_INTERVAL,
+        }
+    }
+}
+
+impl ClientBuilder {
+    /// The base URL of the API; by default `https://texttv.nu/api`.
+    #[must_use]
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// The app ID sent with every request, identifying the client to the API.
+    #[must_use]
+    pub fn app_id(mut self, app_id: impl Into<String>) -> Self {
+        self.app_id = app_id.into();
+        self
+    }
+
+    /// The time to wait for a connection; `None` waits forever.
+    #[must_use]
+    pub fn connect_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.connect_timeout = timeout.into();
+        self
+    }
+
+    /// The time to wait for a response, and again for its body; `None` waits
+    /// forever.
+    #[must_use]
+    pub fn read_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.read_timeout = timeout.into();
+        self
+    }
+
+    /// An HTTP proxy, e.g. `http://proxy.example.com:8080`. Without one,
+    /// the proxy is read from the `HTTP_PROXY` and `HTTPS_PROXY` environment
+    /// variables.
+    #[must_use]
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// The `User-Agent` header; by default `textty/<version>`.
+    #[must_use]
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// The number of times a request is retried after a timeout, a lost
+    /// connection, or a 5xx or 429 response; by default 2.
+    #[must_use]
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// The delay before the first retry, doubled for each following retry,
+    /// with some random jitter; by default 500 ms.
+    #[must_use]
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// The minimum time between the starts of two requests, to stay polite to
+    /// the API; by default 100 ms. Zero disables rate limiting.
+    #[must_use]
+    pub fn min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// An HTTP agent with the settings of the builder, except the base URL
+    /// and the app ID.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the proxy URL is invalid.
+    pub(crate) fn http(&self) -> Result<Http, Error> {
+        let mut config = ureq::Agent::config_builder()
+            .timeout_connect(self.connect_timeout)
+            .timeout_recv_response(self.read_timeout)
+            .timeout_recv_body(self.read_timeout)
+            .user_agent(&self.user_agent);
+        if let Some(proxy) = &self.proxy {
+            config = config.proxy(Some(ureq::Proxy::new(proxy)?));
+        }
+        Ok(Http {
+            agent: ureq::Agent::new_with_config(config.build()),
+            retries: self.retries,
+            retry_delay: self.retry_delay,
+            limiter: RateLimiter::new(self.min_interval),
+        })
+    }
+
+    /// Build the client.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the proxy URL is invalid.
+    pub fn build(self) -> Result<Client, Error> {
+        Ok(Client {
+            http: self.http()?,
+            base_url: self.base_url,
+            app_id: self.app_id,
+        })
     }
 }
 
 impl Client {
-    /// Get a range of pages from `texttv.nu`; from `lo` to `hi`.
+    /// Create a builder for a client with custom HTTP settings.
+    #[must_use]
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// The public host of the web service, e.g. `https://texttv.nu`.
+    fn host(&self) -> &str {
+        self.base_url.strip_suffix("/api").unwrap_or(&self.base_url)
+    }
+
+    /// Get a range of pages from `texttv.nu`; from `lo` to `hi`. The
+    /// validators of the response belong to the range rather than to any of
+    /// the pages, so they are not returned.
     ///
     /// # Errors
     ///
@@ -114,14 +447,15 @@ impl Client {
         if hi < lo {
             return Err(Error::InvalidPageRange { lo: lo.0, hi: hi.0 });
         }
-        let url = format!("{BASE_URL}/get/{}-{}", lo.0, hi.0);
-        let mut response = self
-            .inner
-            .get(&url)
-            .query_pairs([("app", APP_ID), ("includePlainTextContent", "1")])
-            .call()?;
-
-        let pages: Vec<PageResponse> = response.body_mut().read_json()?;
+        let url = format!("{}/get/{}-{}", self.base_url, lo.0, hi.0);
+        let pages: Vec<PageResponse> = self.http.send(|agent| {
+            agent
+                .get(&url)
+                .query_pairs([("app", &*self.app_id), ("includePlainTextContent", "1")])
+                .call()?
+                .body_mut()
+                .read_json()
+        })?;
         Ok(pages)
     }
 
@@ -132,17 +466,109 @@ impl Client {
     ///
     /// * Returns [`ureq::Error`] if API request fails in the network, I/O, or application stack.
     pub fn get_page(&self, number: PageNumber) -> Result<PageResponse, Error> {
-        let url = format!("{BASE_URL}/get/{}", number.0);
-        let mut response = self
-            .inner
-            .get(&url)
-            .query_pairs([("app", APP_ID), ("includePlainTextContent", "1")])
-            .call()?;
-
-        let mut pages: Vec<PageResponse> = response.body_mut().read_json()?;
-        match pages.pop() {
-            Some(page) => Ok(page),
+        self.get_page_with_validators(number).map(|(page, _)| page)
+    }
+
+    /// Get a single page from `texttv.nu`, like [`Client::get_page`], along
+    /// with the validators of the response.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`ureq::Error`] if API request fails in the network, I/O, or application stack.
+    pub fn get_page_with_validators(
+        &self,
+        number: PageNumber,
+    ) -> Result<(PageResponse, Validators), Error> {
+        let fetched = self.fetch_page(number.0, &Validators::default())?;
+        match fetched {
+            Some((mut pages, validators)) => match pages.pop() {
+                Some(page) => Ok((page, validators)),
+                None => Err(Error::InvalidPageNumber(number.0)),
+            },
             None => Err(Error::InvalidPageNumber(number.0)),
         }
     }
+
+    /// Get page `known.num` from `texttv.nu` if it has changed since `known`
+    /// was fetched. The request is conditional on `validators` from the
+    /// earlier response, and the page is also unchanged if the API returns
+    /// the same page state.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`ureq::Error`] if API request fails in the network, I/O, or application stack.
+    pub fn get_page_if_changed(
+        &self,
+        known: &PageResponse,
+        validators: &Validators,
+    ) -> Result<PageUpdate, Error> {
+        let Some((mut pages, validators)) = self.fetch_page(known.num, validators)? else {
+            return Ok(PageUpdate::Unchanged);
+        };
+        match pages.pop() {
+            Some(page) if page.is_same_state(known) => Ok(PageUpdate::Unchanged),
+            Some(page) => Ok(PageUpdate::Changed { page, validators }),
+            None => Err(Error::InvalidPageNumber(known.num)),
+        }
+    }
+
+    /// Get page `number` from `texttv.nu`, along with the validators of the
+    /// response. The request is conditional on `validators`, if any, and
+    /// `None` is returned if the page has not been modified.
+    fn fetch_page(
+        &self,
+        number: u16,
+        validators: &Validators,
+    ) -> Result<Option<(Vec<PageResponse>, Validators)>, Error> {
+        let url = format!("{}/get/{number}", self.base_url);
+        let fetched = self.http.send(|agent| {
+            let mut request = agent
+                .get(&url)
+                .query_pairs([("app", &*self.app_id), ("includePlainTextContent", "1")]);
+            if let Some(etag) = &validators.etag {
+                request = request.header("If-None-Match", etag);
+            }
+            if let Some(last_modified) = &validators.last_modified {
+                request = request.header("If-Modified-Since", last_modified);
+            }
+            let mut response = request.call()?;
+            if response.status() == 304 {
+                return Ok(None);
+            }
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string)
+            };
+            let validators = Validators {
+                etag: header("etag"),
+                last_modified: header("last-modified"),
+            };
+            let pages: Vec<PageResponse> = response.body_mut().read_json()?;
+            Ok(Some((pages, validators)))
+        })?;
+        Ok(fetched)
+    }
+
+    /// Get an image referenced by a page, e.g. a mosaic GIF. The `url` may be
+    /// relative to the host of the API, by default `https://texttv.nu`.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`ureq::Error`] if API request fails in the network, I/O, or application stack.
+    pub fn get_image(&self, url: &str) -> Result<Vec<u8>, Error> {
+        let url = if url.starts_with("//") {
+            let scheme = self.host().split_once(':').map_or("https", |(s, _)| s);
+            format!("{scheme}:{url}")
+        } else if url.starts_with('/') {
+            format!("{}{url}", self.host())
+        } else {
+            url.to_string()
+        };
+        Ok(self
+            .http
+            .send(|agent| agent.get(&url).call()?.body_mut().read_to_vec())?)
+    }
 }
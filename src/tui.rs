@@ -1,25 +1,51 @@
-use crate::{page, texttv};
+use crate::bookmarks::Bookmarks;
+use crate::cache::Cache;
+use crate::cli::{Cli, Glyphs, Prefetch};
+use crate::history::History;
+use crate::source::{self, PageSource};
+use crate::texttv::{PageResponse, Validators};
+use crate::worker::{Fetched, Worker};
+use crate::{diff, mosaic, page};
 use chrono::{DateTime, Local};
-use color_eyre::Result;
+use color_eyre::{Report, Result, eyre::WrapErr};
 use ratatui::{
     DefaultTerminal, Frame,
     buffer::Buffer,
-    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
-    layout::{Constraint, Flex, Layout, Rect},
+    crossterm::event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind,
+    },
+    layout::{Constraint, Flex, Layout, Position, Rect},
     style::{Style, Stylize},
     text::{Line, Span, Text},
     widgets::{Block, Borders, Padding, Paragraph, Widget},
 };
 use std::borrow::Cow;
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
 
-impl From<page::Span> for Span<'_> {
-    fn from(value: page::Span) -> Self {
+/// How long to wait for a terminal event before checking for fetched pages.
+const TICK_RATE: Duration = Duration::from_millis(50);
+
+/// The auto-refresh interval used if none is given on the command line.
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The carousel interval used if none is given on the command line.
+const DEFAULT_CAROUSEL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long rows that changed in a refresh are highlighted.
+const HIGHLIGHT_DURATION: Duration = Duration::from_secs(5);
+
+/// The number of steps in the carousel progress indicator.
+const CAROUSEL_STEPS: u32 = 4;
+
+impl<'a> From<&'a page::Span> for Span<'a> {
+    fn from(value: &'a page::Span) -> Self {
         let mut style = Style::default();
         style.bg = Some(value.style.bg.into());
         style.fg = Some(value.style.fg.into());
         Self {
             style,
-            content: Cow::from(value.content),
+            content: Cow::from(value.content.as_str()),
         }
     }
 }
@@ -54,28 +80,139 @@ impl From<page::FgColour> for ratatui::style::Color {
     }
 }
 
-/// The main application which holds the state and logic of the application.
-#[derive(Debug, Default)]
-pub struct App<'a> {
-    client: texttv::Client,
-    page_set: Vec<Text<'a>>,
+/// The main application which holds the state and logic of the application,
+/// reading pages from a source of type `S`; by default the backend selected
+/// on the command line.
+#[derive(Debug)]
+pub struct App<S = Box<dyn PageSource + Send>> {
+    worker: Worker<S>,
+    pending: Option<Pending>,
+    /// The ID of a background refresh of the current page, if one is running.
+    refreshing: Option<u64>,
+    /// Refreshes the current page in the background, counted from when the
+    /// page was last fetched.
+    auto_refresh: Interval,
+    /// Advances through the page set, counted from when the page was shown
+    /// or the user last scrolled.
+    carousel: Interval,
+    /// The carousel progress currently displayed in the header.
+    carousel_step: u32,
+    /// The lines that changed in the last refresh, highlighted until a key is
+    /// pressed or [`HIGHLIGHT_DURATION`] has passed.
+    highlight: Option<Highlight>,
+    page_set: Vec<page::Page>,
     page_index: usize,
+    selected_link: Option<usize>,
     page_nr: u16,
+    /// The current page as fetched, with its validators, to refresh it with.
+    shown: Option<(PageResponse, Validators)>,
+    title: String,
     next_nr: u16,
     prev_nr: u16,
     updated_unix: i64,
+    cached_unix: Option<i64>,
+    error: Option<ErrorStatus>,
+    history: History,
+    bookmarks: Bookmarks,
+    bookmark_keys: bool,
+    list_selected: usize,
     mode: Mode,
     input_buffer: String,
     use_plain: bool,
+    glyphs: Glyphs,
+    prefetch: Prefetch,
+    /// The range of page numbers of the backend.
+    page_range: RangeInclusive<u16>,
+    /// The terminal area of the last drawn frame; used to locate mouse clicks.
+    area: Rect,
     exit: bool,
 }
 
+/// A page request that the app is waiting on.
+#[derive(Debug, Clone, Copy)]
+struct Pending {
+    id: u64,
+    page_nr: u16,
+    request: Request,
+}
+
+/// The reason a page was requested, which decides how it is shown once fetched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Request {
+    /// Navigation to a page, recorded as a new visit in the history.
+    Navigate,
+    /// Navigation to a position in the history, when going back or forward.
+    History(usize),
+    /// A background refresh of the current page.
+    Refresh,
+}
+
+/// The lines of each page in the page set that changed in a refresh.
+#[derive(Debug)]
+struct Highlight {
+    lines: Vec<Vec<usize>>,
+    until: Instant,
+}
+
+/// A recurring action, e.g. auto-refresh, that can be toggled on and off.
+#[derive(Debug)]
+struct Interval {
+    enabled: bool,
+    period: Duration,
+    start: Instant,
+}
+
+impl Interval {
+    /// Create an interval that is enabled if `seconds` is given, and
+    /// otherwise uses `default` as period when enabled.
+    fn new(seconds: Option<u64>, default: Duration) -> Self {
+        Self {
+            enabled: seconds.is_some(),
+            period: seconds.map_or(default, Duration::from_secs),
+            start: Instant::now(),
+        }
+    }
+
+    /// Turn the interval on or off, and restart it.
+    fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.restart();
+    }
+
+    /// Restart the interval from now.
+    fn restart(&mut self) {
+        self.start = Instant::now();
+    }
+
+    /// Returns `true` if the interval is enabled and the period has passed.
+    fn is_due(&self) -> bool {
+        self.enabled && self.start.elapsed() >= self.period
+    }
+
+    /// The progress towards the end of the period, in `0..=steps`.
+    fn progress(&self, steps: u32) -> u32 {
+        let elapsed = self.start.elapsed().as_millis();
+        let period = self.period.as_millis().max(1);
+        u32::try_from(elapsed * u128::from(steps) / period).map_or(steps, |step| step.min(steps))
+    }
+}
+
+/// An error displayed in the status line, below the page.
+#[derive(Debug)]
+struct ErrorStatus {
+    message: String,
+    /// The page that failed to load, if any; retried on `r`.
+    page_nr: Option<u16>,
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub enum Mode {
     #[default]
     Normal,
     Input,
     Help,
+    History,
+    Bookmarks,
 }
 
 #[derive(Debug, Default)]
@@ -83,6 +220,7 @@ pub struct PageLayout {
     header: Rect,
     content: Rect,
     footer: Rect,
+    status: Rect,
 }
 
 impl From<Rect> for PageLayout {
@@ -90,10 +228,11 @@ impl From<Rect> for PageLayout {
         let [area] = Layout::horizontal([Constraint::Length(40)])
             .flex(Flex::Center)
             .areas(area);
-        let [header, content, footer] = Layout::vertical([
+        let [header, content, footer, status] = Layout::vertical([
             Constraint::Length(2),
             Constraint::Length(24),
             Constraint::Length(2),
+            Constraint::Length(2),
         ])
         .flex(Flex::Center)
         .areas(area);
@@ -101,6 +240,7 @@ impl From<Rect> for PageLayout {
             header,
             content,
             footer,
+            status,
         }
     }
 }
@@ -113,8 +253,19 @@ READING MODE
 →, l  next page
 ↑, k  scroll up
 ↓, j  scroll down
-r     refresh page
-1-8   jump to page 100-800
+Tab   select next link on page
+⇧Tab  select previous link on page
+↵     open selected link
+b     back in history
+f     forward in history
+r     refresh page, or retry on error
+a     toggle auto-refresh
+c     toggle page set carousel
+Esc   cancel loading, dismiss error
+1-8   jump to page 100-800, or bookmark
+m     bookmark page, or remove bookmark
+B     show bookmarks
+H     show history
 ?     show help page
 q     quit application
 
@@ -129,7 +280,10 @@ impl Widget for HelpWidget {
         let [area] = Layout::horizontal([Constraint::Length(40)])
             .flex(Flex::Center)
             .areas(area);
-        let [area] = Layout::vertical([Constraint::Length(24)])
+        // Grow beyond the page height if the help text needs it; the text is
+        // surrounded by a border and padding.
+        let text_height = u16::try_from(HELP_TEXT.lines().count()).unwrap_or(u16::MAX);
+        let [area] = Layout::vertical([Constraint::Length(text_height.saturating_add(4).max(24))])
             .flex(Flex::Center)
             .areas(area);
         let help = Paragraph::new(HELP_TEXT).left_aligned().block(
@@ -142,79 +296,435 @@ impl Widget for HelpWidget {
     }
 }
 
-impl App<'_> {
+/// A list of pages shown as an overlay, e.g. the history or the bookmarks,
+/// with the selected item highlighted.
+struct ListWidget<'a> {
+    title: &'a str,
+    items: Vec<String>,
+    selected: usize,
+}
+
+impl Widget for ListWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let [area] = Layout::horizontal([Constraint::Length(40)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::vertical([Constraint::Length(24)])
+            .flex(Flex::Center)
+            .areas(area);
+        let lines = self
+            .items
+            .into_iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let line = Line::from(item);
+                if i == self.selected {
+                    line.reversed()
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>();
+        // Keep the selected item visible in long lists.
+        let visible = usize::from(area.height.saturating_sub(4));
+        let offset = (self.selected + 1).saturating_sub(visible);
+        let list = Paragraph::new(lines)
+            .scroll((u16::try_from(offset).unwrap_or(u16::MAX), 0))
+            .block(
+                Block::bordered()
+                    .title(Line::from(format!(" {} ", self.title)).left_aligned())
+                    .title(Line::from(" Esc to Close ").right_aligned())
+                    .padding(Padding::uniform(1)),
+            );
+        list.render(area, buf);
+    }
+}
+
+impl App {
+    /// Create an app that reads pages from the backend selected in `args`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the HTTP settings in `args` are invalid.
+    pub fn new(args: &Cli) -> Result<Self> {
+        let source = source::for_backend(args.backend, &args.client_settings())?;
+        let cache = Cache::open_default(args.backend.cache_name());
+        Ok(Self::with_source(args, source, cache))
+    }
+}
+
+impl<S> App<S>
+where
+    S: PageSource + Send + 'static,
+{
+    /// Create an app that reads pages from `source`, e.g. a directory of
+    /// saved pages, and stores fetched pages in `cache`, which is used as a
+    /// fallback. Without a cache, pages are only read from `source`.
     #[must_use]
-    pub fn new(plain: bool) -> Self {
+    pub fn with_source(args: &Cli, source: S, cache: Option<Cache>) -> Self {
+        let worker = Worker::spawn(source, cache, args.offline);
+        // Start without bookmarks if they cannot be loaded, and report the error.
+        let (bookmarks, error) = match Bookmarks::open_default() {
+            Ok(bookmarks) => (bookmarks, None),
+            Err(err) => {
+                let error = ErrorStatus {
+                    message: format!("could not load bookmarks: {err}"),
+                    page_nr: None,
+                };
+                (Bookmarks::default(), Some(error))
+            }
+        };
         Self {
-            page_nr: texttv::HOME_PAGE_NR,
-            use_plain: plain,
-            ..Default::default()
+            worker,
+            pending: None,
+            refreshing: None,
+            auto_refresh: Interval::new(args.refresh, DEFAULT_REFRESH_INTERVAL),
+            carousel: Interval::new(args.carousel, DEFAULT_CAROUSEL_INTERVAL),
+            carousel_step: 0,
+            highlight: None,
+            page_set: Vec::new(),
+            page_index: 0,
+            selected_link: None,
+            page_nr: args.backend.home_page(),
+            shown: None,
+            title: String::new(),
+            next_nr: 0,
+            prev_nr: 0,
+            updated_unix: 0,
+            cached_unix: None,
+            error,
+            history: History::default(),
+            bookmarks,
+            bookmark_keys: args.bookmark_keys,
+            list_selected: 0,
+            mode: Mode::default(),
+            input_buffer: String::new(),
+            use_plain: args.plain,
+            glyphs: args.glyphs,
+            prefetch: args.prefetch,
+            page_range: args.backend.page_range(),
+            area: Rect::default(),
+            exit: false,
         }
     }
 
-    /// Fetches the current page from the web, parses the page set into
-    /// [`Text`] objects, and updates the app state.
-    fn get_current_page(&mut self) -> Result<()> {
-        let response = self
-            .client
-            .get_page(texttv::PageNumber::from(self.page_nr))?;
-        self.next_nr = response.next_page;
-        self.prev_nr = response.prev_page;
-        self.page_index = 0;
-        self.updated_unix = response.date_updated_unix;
+    /// Requests page `page_nr` from the worker. The current page is displayed
+    /// until the requested page has been fetched. A new request replaces any
+    /// pending request.
+    fn load_page(&mut self, page_nr: u16) {
+        self.request_page(page_nr, Request::Navigate);
+    }
+
+    /// Requests page `page_nr` from the worker, for the given `request`. Any
+    /// background refresh is abandoned, since the current page is replaced.
+    fn request_page(&mut self, page_nr: u16, request: Request) {
+        let id = self.worker.request(page_nr);
+        self.refreshing = None;
+        self.pending = Some(Pending {
+            id,
+            page_nr,
+            request,
+        });
+    }
+
+    /// Requests the page at `position` in the history. The position within
+    /// the page set is restored once the page has been fetched.
+    fn load_history(&mut self, position: usize) {
+        let Some(entry) = self.history.get(position) else {
+            return;
+        };
+        self.request_page(entry.page_nr, Request::History(position));
+    }
+
+    /// Starts a background refresh of the current page, if auto-refresh is
+    /// enabled and the refresh interval has passed since the page was fetched.
+    /// No refresh is started while another page is loading.
+    fn auto_refresh(&mut self) {
+        if !self.auto_refresh.is_due() || self.pending.is_some() || self.refreshing.is_some() {
+            return;
+        }
+        if let Some((page, validators)) = &self.shown {
+            self.refreshing = Some(self.worker.refresh(page.clone(), validators.clone()));
+        }
+    }
+
+    /// Fetches the neighbouring pages of the current page in the background,
+    /// and the bookmarked pages if enabled, so that they show at once.
+    fn prefetch(&self) {
+        let mut pages = match self.prefetch {
+            Prefetch::Off => return,
+            Prefetch::Neighbours => Vec::new(),
+            Prefetch::Bookmarks => self.bookmarks.pages().iter().map(|b| b.num).collect(),
+        };
+        pages.extend([self.prev_nr, self.next_nr]);
+        pages.retain(|&page_nr| page_nr != self.page_nr);
+        self.worker.prefetch(pages);
+    }
+
+    /// Go back in the history.
+    fn history_back(&mut self) {
+        if let Some(position) = self.history.back() {
+            self.load_history(position);
+        }
+    }
+
+    /// Go forward in the history.
+    fn history_forward(&mut self) {
+        if let Some(position) = self.history.forward() {
+            self.load_history(position);
+        }
+    }
+
+    /// Cancels the pending page request, if any.
+    const fn cancel_loading(&mut self) {
+        self.pending = None;
+    }
+
+    /// Reloads the page that failed to load. If there is no such page, the
+    /// current page is refreshed like [`App::auto_refresh`] does, replacing
+    /// any pending request.
+    fn retry(&mut self) {
+        if let Some(page_nr) = self.error.take().and_then(|error| error.page_nr) {
+            self.load_page(page_nr);
+        } else if let Some((page, validators)) = &self.shown {
+            self.pending = None;
+            self.refreshing = Some(self.worker.refresh(page.clone(), validators.clone()));
+        } else {
+            self.load_page(self.page_nr);
+        }
+    }
+
+    /// Displays `report` in the status line. The app stays on the current page.
+    fn show_error(&mut self, report: &Report, page_nr: Option<u16>) {
+        self.error = Some(ErrorStatus {
+            message: format!("{report:#}"),
+            page_nr,
+        });
+    }
+
+    /// Handles pages fetched by the worker. Results of cancelled or
+    /// replaced requests are discarded. Returns `true` if the app state
+    /// changed, i.e. if the user interface must be redrawn.
+    fn handle_fetched_pages(&mut self) -> bool {
+        let mut changed = false;
+        while let Some(fetched) = self.worker.try_recv() {
+            let request = if self.refreshing == Some(fetched.id) {
+                self.refreshing = None;
+                Request::Refresh
+            } else if let Some(pending) = self.pending.filter(|p| p.id == fetched.id) {
+                self.pending = None;
+                pending.request
+            } else {
+                continue;
+            };
+            self.auto_refresh.restart();
+
+            if request == Request::Refresh {
+                // Keep the page as it is if the refresh failed or the page is unchanged;
+                // a failed background refresh is retried on the next interval.
+                let Ok(fetch) = &fetched.result else {
+                    continue;
+                };
+                let response = &fetch.response;
+                if !fetch.changed
+                    || self
+                        .shown
+                        .as_ref()
+                        .is_some_and(|(shown, _)| response.is_same_state(shown))
+                {
+                    continue;
+                }
+            }
+
+            let page_nr = fetched.page_nr;
+            changed = true;
+            match self
+                .show_page(fetched, request)
+                .wrap_err_with(|| format!("could not load page {page_nr}"))
+            {
+                Ok(()) => self.error = None,
+                Err(report) => self.show_error(&report, Some(page_nr)),
+            }
+        }
+        changed
+    }
+
+    /// Parses the page set of a fetched page into [`page::Page`] objects, and
+    /// updates the app state. The state is left unchanged on error.
+    ///
+    /// If the page was requested from the history, the history position and
+    /// the position within the page set are restored. A refreshed page keeps
+    /// the position within the page set. Otherwise the page is recorded as a
+    /// new visit.
+    fn show_page(&mut self, fetched: Fetched, request: Request) -> Result<()> {
+        let fetch = fetched.result?;
+        let response = fetch.response;
 
         let mut page_set = Vec::with_capacity(response.content.len());
         if self.use_plain {
-            if let Some(content_plain) = response.content_plain {
+            if let Some(content_plain) = &response.content_plain {
                 for content in content_plain {
-                    page_set.push(Text::from(content));
+                    page_set.push(page::Page::from_plain(content, &self.page_range));
                 }
             }
         } else {
             for content in &response.content {
-                let raw_page = page::parse(content)?;
-                let text = Text::from(
-                    raw_page
-                        .into_iter()
-                        .map(|line| {
-                            Line::from(line.into_iter().map(Span::from).collect::<Vec<_>>())
-                        })
-                        .collect::<Vec<_>>(),
-                );
-                page_set.push(text);
+                // Mosaics are drawn with the glyphs when rendered, since the
+                // halves of double-height mosaics are taken from the sextants.
+                page_set.push(page::parse(content, &self.page_range)?);
             }
         }
 
+        self.highlight = match request {
+            Request::Refresh => {
+                let lines = diff::changed_page_set(&self.page_set, &page_set);
+                lines
+                    .iter()
+                    .any(|lines| !lines.is_empty())
+                    .then(|| Highlight {
+                        lines,
+                        until: Instant::now() + HIGHLIGHT_DURATION,
+                    })
+            }
+            Request::Navigate | Request::History(_) => None,
+        };
         self.page_set = page_set;
+        self.selected_link = None;
+        self.page_nr = fetched.page_nr;
+        self.title.clone_from(&response.title);
+        self.next_nr = response.next_page;
+        self.prev_nr = response.prev_page;
+        self.updated_unix = response.date_updated_unix;
+        self.cached_unix = fetch.cached_unix;
+
+        let last_index = self.page_set.len().saturating_sub(1);
+        match request {
+            Request::Navigate => {
+                self.page_index = 0;
+                self.history.visit(self.page_nr, &response.title);
+                self.carousel.restart();
+                self.prefetch();
+            }
+            Request::History(position) => {
+                self.history.go_to(position);
+                self.page_index = self
+                    .history
+                    .get(position)
+                    .map_or(0, |entry| entry.page_index.min(last_index));
+                self.carousel.restart();
+                self.prefetch();
+            }
+            Request::Refresh => self.page_index = self.page_index.min(last_index),
+        }
+        self.shown = Some((response, fetch.validators));
         Ok(())
     }
 
     /// Go to next page.
-    fn next_page(&mut self) -> Result<()> {
-        self.page_nr = self.next_nr;
-        self.get_current_page()?;
-        Ok(())
+    fn next_page(&mut self) {
+        self.load_page(self.next_nr);
     }
 
     /// Go to previous page.
-    fn prev_page(&mut self) -> Result<()> {
-        self.page_nr = self.prev_nr;
-        self.get_current_page()?;
-        Ok(())
+    fn prev_page(&mut self) {
+        self.load_page(self.prev_nr);
     }
 
-    /// Go to previous page in the page set.
-    const fn scroll_prev(&mut self) {
+    /// Go to previous page in the page set. The carousel is paused for
+    /// another interval.
+    fn scroll_prev(&mut self) {
         if self.page_index > 0 {
             self.page_index -= 1;
+            self.selected_link = None;
         }
+        self.history.set_page_index(self.page_index);
+        self.carousel.restart();
     }
 
-    /// Go to next page in the page set.
-    const fn scroll_next(&mut self) {
+    /// Go to next page in the page set. The carousel is paused for
+    /// another interval.
+    fn scroll_next(&mut self) {
         let n_pages = self.page_set.len();
         if n_pages > 1 && self.page_index < n_pages - 1 {
             self.page_index += 1;
+            self.selected_link = None;
+        }
+        self.history.set_page_index(self.page_index);
+        self.carousel.restart();
+    }
+
+    /// Returns `true` if the carousel is running, i.e. if it is enabled and
+    /// there is more than one page in the page set.
+    fn carousel_running(&self) -> bool {
+        self.carousel.enabled && self.page_set.len() > 1
+    }
+
+    /// Advances the carousel to the next page in the page set when its
+    /// interval has passed, looping at the end. Returns `true` if the page or
+    /// the progress indicator changed, i.e. if the user interface must be
+    /// redrawn.
+    fn carousel_tick(&mut self) -> bool {
+        if !self.carousel_running() {
+            return false;
+        }
+        if self.carousel.is_due() {
+            self.page_index = (self.page_index + 1) % self.page_set.len();
+            self.selected_link = None;
+            self.history.set_page_index(self.page_index);
+            self.carousel.restart();
+        }
+        let step = self.carousel.progress(CAROUSEL_STEPS);
+        std::mem::replace(&mut self.carousel_step, step) != step
+    }
+
+    /// Removes the highlight of changed lines once it has expired. Returns
+    /// `true` if it was removed, i.e. if the user interface must be redrawn.
+    fn highlight_tick(&mut self) -> bool {
+        if self
+            .highlight
+            .as_ref()
+            .is_some_and(|highlight| highlight.until <= Instant::now())
+        {
+            self.highlight = None;
+            return true;
+        }
+        false
+    }
+
+    /// The links on the displayed page in the page set.
+    fn links(&self) -> &[page::Link] {
+        self.page_set
+            .get(self.page_index)
+            .map_or(&[], |page| page.links.as_slice())
+    }
+
+    /// Select the next link on the page, wrapping around at the end.
+    fn select_next_link(&mut self) {
+        let n_links = self.links().len();
+        if n_links > 0 {
+            self.selected_link = Some(self.selected_link.map_or(0, |i| (i + 1) % n_links));
+        }
+    }
+
+    /// Select the previous link on the page, wrapping around at the start.
+    fn select_prev_link(&mut self) {
+        let n_links = self.links().len();
+        if n_links > 0 {
+            self.selected_link = Some(
+                self.selected_link
+                    .map_or(n_links - 1, |i| (i + n_links - 1) % n_links),
+            );
+        }
+    }
+
+    /// Go to the page referenced by the selected link.
+    fn open_selected_link(&mut self) {
+        let target = self
+            .selected_link
+            .and_then(|i| self.links().get(i))
+            .map(|link| link.target);
+        if let Some(target) = target {
+            self.load_page(target);
         }
     }
 
@@ -225,11 +735,22 @@ impl App<'_> {
     /// Will return any `Err` that may occur in the application.
     pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
         // Get home page on startup.
-        self.get_current_page()?;
+        self.load_page(self.page_nr);
 
+        // Only redraw after events or when the page state changed.
+        let mut redraw = true;
         while !self.exit {
-            terminal.draw(|frame| self.render_ui(frame))?;
-            self.handle_crossterm_events()?;
+            if redraw {
+                terminal.draw(|frame| {
+                    self.area = frame.area();
+                    self.render_ui(frame);
+                })?;
+            }
+            redraw = self.handle_crossterm_events()?;
+            redraw |= self.handle_fetched_pages();
+            redraw |= self.carousel_tick();
+            redraw |= self.highlight_tick();
+            self.auto_refresh();
         }
         Ok(())
     }
@@ -244,19 +765,74 @@ impl App<'_> {
                 let hw = HelpWidget {};
                 frame.render_widget(hw, frame.area());
             }
+            Mode::History => {
+                let items = self
+                    .history
+                    .entries()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, entry)| {
+                        let marker = if i == self.history.position() {
+                            '▶'
+                        } else {
+                            ' '
+                        };
+                        format!("{marker} {:>3}  {}", entry.page_nr, entry.title)
+                    })
+                    .collect();
+                let lw = ListWidget {
+                    title: "History",
+                    items,
+                    selected: self.list_selected,
+                };
+                frame.render_widget(lw, frame.area());
+            }
+            Mode::Bookmarks => {
+                let items = self
+                    .bookmarks
+                    .pages()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, bookmark)| {
+                        // Mark the bookmarks that can be reached with the number keys.
+                        let slot = if self.bookmark_keys && i < 8 {
+                            (i + 1).to_string()
+                        } else {
+                            String::new()
+                        };
+                        format!("{slot:>1} {:>3}  {}", bookmark.num, bookmark.title)
+                    })
+                    .collect();
+                let lw = ListWidget {
+                    title: "Bookmarks",
+                    items,
+                    selected: self.list_selected,
+                };
+                frame.render_widget(lw, frame.area());
+            }
         }
     }
 
-    /// Reads the crossterm events and updates the state of [`App`].
-    fn handle_crossterm_events(&mut self) -> Result<()> {
+    /// Reads the crossterm events and updates the state of [`App`]. Waits
+    /// at most [`TICK_RATE`] for an event, so that fetched pages are handled
+    /// while there is no user input. Returns `true` if an event was read.
+    fn handle_crossterm_events(&mut self) -> Result<bool> {
+        if !event::poll(TICK_RATE)? {
+            return Ok(false);
+        }
         match event::read()? {
             // it's important to check KeyEventKind::Press to avoid handling key release events
-            Event::Key(key) if key.kind == KeyEventKind::Press => self.on_key_event(key)?,
-            // Event::Mouse(_) => {}
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                self.highlight = None;
+                if let Err(report) = self.on_key_event(key) {
+                    self.show_error(&report, None);
+                }
+            }
+            Event::Mouse(mouse) => self.on_mouse_event(mouse),
             // Event::Resize(_, _) => {}
             _ => {}
         }
-        Ok(())
+        Ok(true)
     }
 
     /// Handles the key events and updates the state of [`App`].
@@ -268,19 +844,77 @@ impl App<'_> {
                 self.handle_key_event_help(key.code);
                 Ok(())
             }
+            Mode::History | Mode::Bookmarks => self.handle_key_event_list(key.code),
+        }
+    }
+
+    /// Handles the mouse events and updates the state of [`App`]. Mouse
+    /// events are only handled in normal mode.
+    fn on_mouse_event(&mut self, mouse: MouseEvent) {
+        if self.mode != Mode::Normal {
+            return;
+        }
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => self.on_click(mouse.column, mouse.row),
+            MouseEventKind::ScrollUp => self.scroll_prev(),
+            MouseEventKind::ScrollDown => self.scroll_next(),
+            _ => {}
+        }
+    }
+
+    /// Handle a click at `column`, `row`: the prev/next page in the header, or
+    /// a page number in the page content.
+    fn on_click(&mut self, column: u16, row: u16) {
+        let layout = PageLayout::from(self.area);
+        if row == layout.header.y {
+            // See the header layout in `render`: ` M..M 099 ◀ 100 ▶ 101 ...`.
+            match column.checked_sub(layout.header.x) {
+                Some(13..=17) => self.prev_page(),
+                Some(23..=27) => self.next_page(),
+                _ => {}
+            }
+        } else if layout.content.contains(Position::new(column, row)) {
+            if let Some(target) = self.link_at(layout.content, column, row) {
+                self.load_page(target);
+            }
+        }
+    }
+
+    /// The target of the link rendered at `column`, `row` within `content`, if any.
+    fn link_at(&self, content: Rect, column: u16, row: u16) -> Option<u16> {
+        let page = self.page_set.get(self.page_index)?;
+        let display_row = page
+            .display_rows()
+            .get(usize::from(row - content.y))
+            .copied()?;
+        let line = page.lines.get(display_row.line())?;
+        let widths = line
+            .iter()
+            .map(|span| Span::from(span).width())
+            .collect::<Vec<_>>();
+        // Lines are centered, in the same way as in `Paragraph::centered`.
+        let line_width = u16::try_from(widths.iter().sum::<usize>()).unwrap_or(u16::MAX);
+        let offset = (content.width / 2).saturating_sub(line_width / 2);
+        let mut x = usize::from((column - content.x).checked_sub(offset)?);
+        for (span, width) in line.iter().zip(widths) {
+            if x < width {
+                return span.link;
+            }
+            x -= width;
         }
+        None
     }
 
     /// Handle valid events in normal mode.
     fn handle_key_event_normal(&mut self, code: KeyCode) -> Result<()> {
         match code {
             KeyCode::Right | KeyCode::Char('l') => {
-                self.page_nr = self.next_nr;
-                self.next_page()
+                self.next_page();
+                Ok(())
             }
             KeyCode::Left | KeyCode::Char('h') => {
-                self.page_nr = self.prev_nr;
-                self.prev_page()
+                self.prev_page();
+                Ok(())
             }
             KeyCode::Up | KeyCode::Char('k') => {
                 self.scroll_prev();
@@ -293,8 +927,11 @@ impl App<'_> {
             KeyCode::Char(a) if a.is_ascii_digit() => {
                 if let Some(i) = a.to_digit(10) {
                     if (1..9).contains(&i) {
-                        self.page_nr = u16::try_from(i)? * 100;
-                        return self.get_current_page();
+                        if self.bookmark_keys {
+                            self.open_bookmark(usize::try_from(i)? - 1);
+                        } else {
+                            self.load_page(u16::try_from(i)? * 100);
+                        }
                     }
                 }
                 Ok(())
@@ -304,11 +941,58 @@ impl App<'_> {
                 self.input_buffer.clear();
                 Ok(())
             }
+            KeyCode::Tab => {
+                self.select_next_link();
+                Ok(())
+            }
+            KeyCode::BackTab => {
+                self.select_prev_link();
+                Ok(())
+            }
+            KeyCode::Enter => {
+                self.open_selected_link();
+                Ok(())
+            }
+            KeyCode::Char('b') => {
+                self.history_back();
+                Ok(())
+            }
+            KeyCode::Char('f') => {
+                self.history_forward();
+                Ok(())
+            }
+            KeyCode::Char('H') => {
+                self.mode = Mode::History;
+                self.list_selected = self.history.position();
+                Ok(())
+            }
+            KeyCode::Char('m') => self.toggle_bookmark(),
+            KeyCode::Char('a') => {
+                self.auto_refresh.toggle();
+                Ok(())
+            }
+            KeyCode::Char('c') => {
+                self.carousel.toggle();
+                Ok(())
+            }
+            KeyCode::Char('B') => {
+                self.mode = Mode::Bookmarks;
+                self.list_selected = 0;
+                Ok(())
+            }
             KeyCode::Char('?') => {
                 self.mode = Mode::Help;
                 Ok(())
             }
-            KeyCode::Char('r') => self.get_current_page(),
+            KeyCode::Char('r') => {
+                self.retry();
+                Ok(())
+            }
+            KeyCode::Esc => {
+                self.cancel_loading();
+                self.error = None;
+                Ok(())
+            }
             KeyCode::Char('q') => {
                 self.quit();
                 Ok(())
@@ -332,12 +1016,15 @@ impl App<'_> {
                 Ok(())
             }
             KeyCode::Enter => {
-                let requested_page = self.input_buffer.parse::<u16>()?;
-                // Wrap page number to valid range.
-                self.page_nr = requested_page.clamp(texttv::MIN_PAGE_NR, texttv::MAX_PAGE_NR);
-                self.input_buffer.clear();
                 self.mode = Mode::Normal;
-                self.get_current_page()
+                let input = std::mem::take(&mut self.input_buffer);
+                let requested_page = input
+                    .parse::<u16>()
+                    .wrap_err_with(|| format!("invalid page number '{input}'"))?;
+                // Wrap page number to valid range.
+                let range = &self.page_range;
+                self.load_page(requested_page.clamp(*range.start(), *range.end()));
+                Ok(())
             }
             KeyCode::Esc => {
                 self.mode = Mode::Normal;
@@ -354,13 +1041,63 @@ impl App<'_> {
         }
     }
 
+    /// Handle events valid in the list modes, i.e. the history and the bookmarks.
+    fn handle_key_event_list(&mut self, code: KeyCode) -> Result<()> {
+        let n_items = match self.mode {
+            Mode::History => self.history.entries().len(),
+            Mode::Bookmarks => self.bookmarks.pages().len(),
+            _ => 0,
+        };
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.list_selected = self.list_selected.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') if self.list_selected + 1 < n_items => {
+                self.list_selected += 1;
+            }
+            KeyCode::Enter => {
+                let mode = std::mem::take(&mut self.mode);
+                match mode {
+                    Mode::History => self.load_history(self.list_selected),
+                    Mode::Bookmarks => self.open_bookmark(self.list_selected),
+                    _ => {}
+                }
+            }
+            KeyCode::Char('d') if self.mode == Mode::Bookmarks => {
+                self.bookmarks.remove(self.list_selected);
+                self.list_selected = self.list_selected.min(n_items.saturating_sub(2));
+                self.bookmarks.save().wrap_err("could not save bookmarks")?;
+            }
+            KeyCode::Esc => self.mode = Mode::Normal,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Bookmark the current page, or remove its bookmark, and save the bookmarks.
+    fn toggle_bookmark(&mut self) -> Result<()> {
+        self.bookmarks.toggle(self.page_nr, &self.title);
+        self.bookmarks.save().wrap_err("could not save bookmarks")?;
+        Ok(())
+    }
+
+    /// Go to the bookmark at `index`, if any.
+    fn open_bookmark(&mut self, index: usize) {
+        if let Some(bookmark) = self.bookmarks.pages().get(index) {
+            self.load_page(bookmark.num);
+        }
+    }
+
     /// Quit the application.
     const fn quit(&mut self) {
         self.exit = true;
     }
 }
 
-impl Widget for &App<'_> {
+impl<S> Widget for &App<S>
+where
+    S: PageSource + Send + 'static,
+{
     fn render(self, area: Rect, buf: &mut Buffer) {
         let layout = PageLayout::from(area);
 
@@ -369,17 +1106,50 @@ impl Widget for &App<'_> {
         // M------------099-<-100->-101---------1/3
         // |             |     |     |           |
         // margin      prev  curr  next   index/set
+        //
+        // The index is preceded by a progress indicator when the carousel runs.
+        //
+        // The margin marks bookmarked pages and auto-refresh, and shows the
+        // requested page while it is loading.
 
         // In command-mode, display the input buffer instead of current page.
         let current_page_str = match self.mode {
             Mode::Normal => self.page_nr.to_string(),
             Mode::Input => self.input_buffer.clone(),
-            Mode::Help => String::new(), // FIXME: Remove.
+            Mode::Help | Mode::History | Mode::Bookmarks => String::new(), // FIXME: Remove.
+        };
+        let mut scroll_indicator = if self.page_set.is_empty() {
+            String::new()
+        } else {
+            format!("{}/{}", self.page_index + 1, self.page_set.len())
         };
-        let scroll_indicator = format!("{}/{}", self.page_index + 1, self.page_set.len());
+        // Show the progress towards the next page in the carousel, e.g. `▰▰▱▱ 2/5`.
+        if self.carousel_running() {
+            let filled = self.carousel_step.min(CAROUSEL_STEPS) as usize;
+            let empty = CAROUSEL_STEPS as usize - filled;
+            scroll_indicator = format!(
+                "{}{} {scroll_indicator}",
+                "▰".repeat(filled),
+                "▱".repeat(empty)
+            );
+        }
+        let mut margin = Vec::new();
+        if self.bookmarks.contains(self.page_nr) {
+            margin.push("★".to_string());
+        }
+        if self.auto_refresh.enabled {
+            margin.push("↻".to_string());
+        }
+        if let Some(pending) = self.pending {
+            margin.push(format!("⟳ {}", pending.page_nr));
+        }
         let header = Paragraph::new(format!(
             " {:<12}{:>3} ◀ {:>3} ▶ {:>3}{:>12}",
-            "", self.prev_nr, current_page_str, self.next_nr, scroll_indicator,
+            margin.join(" "),
+            self.prev_nr,
+            current_page_str,
+            self.next_nr,
+            scroll_indicator,
         ))
         .block(
             Block::new()
@@ -388,19 +1158,125 @@ impl Widget for &App<'_> {
         );
         header.render(layout.header, buf);
 
-        // The current page content.
-        let content = Paragraph::new(self.page_set[self.page_index].clone()).centered();
-        content.render(layout.content, buf);
+        // The current page content; empty until the first page has been fetched.
+        if let Some(page) = self.page_set.get(self.page_index) {
+            let selected = self.selected_link.and_then(|i| page.links.get(i));
+            let changed = self
+                .highlight
+                .as_ref()
+                .and_then(|highlight| highlight.lines.get(self.page_index))
+                .map_or(&[][..], Vec::as_slice);
+            let content =
+                Paragraph::new(page_text(page, self.glyphs, selected, changed)).centered();
+            content.render(layout.content, buf);
+        }
 
-        // Add page updated timestamp as page footer.
-        let updated = match DateTime::from_timestamp(self.updated_unix, 0) {
-            Some(dt) => dt.with_timezone(&Local).format("%H:%M").to_string(),
-            None => "N/A".to_string(),
+        // Add page updated timestamp as page footer, and mark pages read from the cache.
+        let updated = format_time(self.updated_unix);
+        let footer_text = match self.cached_unix {
+            Some(cached_unix) => format!(
+                "Sidan uppdaterad: {updated} (cached at {})",
+                format_time(cached_unix)
+            ),
+            None => format!("Sidan uppdaterad: {updated}"),
         };
-        let footer = Paragraph::new(format!("Sidan uppdaterad: {updated}"))
+        let footer = Paragraph::new(footer_text)
             .centered()
             .dim()
             .block(Block::new().borders(Borders::TOP));
         footer.render(layout.footer, buf);
+
+        // Display any error in a status line below the page.
+        if let Some(error) = &self.error {
+            let status = Paragraph::new(vec![
+                Line::from(error.message.as_str()).red(),
+                Line::from("r to retry, Esc to dismiss").dim(),
+            ])
+            .centered();
+            status.render(layout.status, buf);
+        }
+    }
+}
+
+/// Convert `page` to [`Text`] that can be rendered, with mosaics drawn with
+/// `glyphs`, and the `selected` link and the `changed` lines highlighted.
+fn page_text<'a>(
+    page: &'a page::Page,
+    glyphs: Glyphs,
+    selected: Option<&page::Link>,
+    changed: &[usize],
+) -> Text<'a> {
+    let lines = page
+        .display_rows()
+        .into_iter()
+        .map(|row| {
+            let i = row.line();
+            let spans = page.lines[i]
+                .iter()
+                .enumerate()
+                .map(|(j, span)| {
+                    let span = match row {
+                        page::DisplayRow::Single(_) => single_height_span(span, glyphs),
+                        page::DisplayRow::Upper(_) => double_height_span(span, false, glyphs),
+                        page::DisplayRow::Lower(_) => double_height_span(span, true, glyphs),
+                    };
+                    if selected.is_some_and(|link| link.line == i && link.span == j) {
+                        span.reversed()
+                    } else {
+                        span
+                    }
+                })
+                .collect::<Vec<_>>();
+            // Changed lines are underlined, since a selected link is reversed.
+            if changed.contains(&i) {
+                Line::from(spans).underlined()
+            } else {
+                Line::from(spans)
+            }
+        })
+        .collect::<Vec<_>>();
+    Text::from(lines)
+}
+
+/// A span in a single-height line, with mosaics drawn with `glyphs`.
+fn single_height_span(span: &page::Span, glyphs: Glyphs) -> Span<'_> {
+    let mut converted = Span::from(span);
+    if span.style.is_mosaic() && glyphs != Glyphs::Sextant {
+        converted.content = span
+            .content
+            .chars()
+            .map(|c| mosaic::convert(c, glyphs))
+            .collect::<String>()
+            .into();
+    }
+    converted
+}
+
+/// The upper or lower half of a span in a double-height line. Mosaics are
+/// split into halves and drawn with `glyphs`; text can't be split, so it is
+/// drawn on the upper row, and the lower row only keeps its background.
+fn double_height_span(span: &page::Span, lower: bool, glyphs: Glyphs) -> Span<'_> {
+    let mut half = Span::from(span);
+    if span.style.is_mosaic() {
+        half.content = span
+            .content
+            .chars()
+            .map(|c| mosaic::double_height(c, lower, glyphs))
+            .collect::<String>()
+            .into();
+    } else if lower {
+        half.content = " ".repeat(half.width()).into();
+    } else {
+        half = half.bold();
+    }
+    half
+}
+
+/// Format a UNIX timestamp as local time, e.g. `13:37`.
+fn format_time(unix: i64) -> String {
+    // Pages from services without an update time have a timestamp of 0.
+    match DateTime::from_timestamp(unix, 0).filter(|_| unix > 0) {
+        Some(dt) => dt.with_timezone(&Local).format("%H:%M").to_string(),
+        None => "N/A".to_string(),
     }
 }
@@ -0,0 +1,610 @@
+use crate::cache::Cache;
+use crate::error::Error;
+use crate::source::PageSource;
+use crate::texttv::{PageResponse, PageUpdate, Validators};
+use crate::{mosaic, page};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::RangeInclusive;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a prefetched page is shown without fetching it again.
+const PREFETCH_TTL: Duration = Duration::from_secs(30);
+
+/// A request for the worker.
+#[derive(Debug)]
+enum Job {
+    /// Fetch a page to show it. With `shown`, the page as it is shown along
+    /// with its validators, the page is fetched again if it has changed.
+    /// Otherwise, a fresh prefetched copy of the page is used if there is one.
+    Fetch {
+        id: u64,
+        page_nr: u16,
+        shown: Option<Box<(PageResponse, Validators)>>,
+    },
+    /// Fetch pages in the background, so that they can be shown without
+    /// waiting when requested.
+    Prefetch(Vec<u16>),
+}
+
+/// Pages fetched recently with their validators, along with the time they
+/// were fetched.
+#[derive(Debug, Default)]
+struct Prefetched {
+    pages: HashMap<u16, (Instant, PageResponse, Validators)>,
+}
+
+impl Prefetched {
+    /// Returns the copy of page `page_nr` if it was fetched within
+    /// [`PREFETCH_TTL`].
+    fn get(&mut self, page_nr: u16) -> Option<(PageResponse, Validators)> {
+        self.pages
+            .retain(|_, (fetched, _, _)| fetched.elapsed() < PREFETCH_TTL);
+        self.pages
+            .get(&page_nr)
+            .map(|(_, page, validators)| (page.clone(), validators.clone()))
+    }
+
+    fn insert(&mut self, page: PageResponse, validators: Validators) {
+        self.pages
+            .insert(page.num, (Instant::now(), page, validators));
+    }
+}
+
+/// Groups `pages` into ranges of consecutive page numbers.
+fn contiguous_ranges(mut pages: Vec<u16>) -> Vec<RangeInclusive<u16>> {
+    pages.sort_unstable();
+    pages.dedup();
+    let mut ranges: Vec<RangeInclusive<u16>> = Vec::new();
+    for page_nr in pages {
+        match ranges.last_mut() {
+            Some(range) if range.end().checked_add(1) == Some(page_nr) => {
+                *range = *range.start()..=page_nr;
+            }
+            _ => ranges.push(page_nr..=page_nr),
+        }
+    }
+    ranges
+}
+
+/// A page fetched by the worker, either from the web or from the cache.
+#[derive(Debug)]
+pub struct Fetch {
+    /// The fetched page.
+    pub response: PageResponse,
+
+    /// The validators of the page, to refresh it with.
+    pub validators: Validators,
+
+    /// A UNIX timestamp representing the time the page was cached, if the
+    /// page was read from the cache rather than from the web.
+    pub cached_unix: Option<i64>,
+
+    /// Whether the page has changed since it was last fetched; only `false`
+    /// for a refresh of an unchanged page.
+    pub changed: bool,
+}
+
+/// The result of a request, sent back from the worker.
+#[derive(Debug)]
+pub struct Fetched {
+    /// The ID returned by [`Worker::request`] for the request.
+    pub id: u64,
+
+    /// The requested page number.
+    pub page_nr: u16,
+
+    /// The fetched page, or the error that occurred when fetching it.
+    pub result: Result<Fetch, Error>,
+}
+
+/// A background thread that fetches pages from a source of type `S`, so that
+/// the UI does not block while waiting on the network.
+#[derive(Debug)]
+pub struct Worker<S> {
+    jobs: Sender<Job>,
+    results: Receiver<Fetched>,
+    next_id: u64,
+    /// The source is owned by the worker thread.
+    source: PhantomData<fn() -> S>,
+}
+
+impl<S> Worker<S>
+where
+    S: PageSource + Send + 'static,
+{
+    /// Spawn a worker thread that fetches pages from `source`. Fetched pages
+    /// and decoded mosaics are stored in `cache`, and the cache is used as a
+    /// fallback when a request fails. If `offline` is set, pages are only
+    /// read from the cache.
+    #[must_use]
+    pub fn spawn(source: S, cache: Option<Cache>, offline: bool) -> Self {
+        load_mosaics(cache.as_ref());
+        let (job_tx, job_rx) = mpsc::channel::<Job>();
+        let (result_tx, result_rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut prefetched = Prefetched::default();
+            let mut queue = Vec::new();
+            loop {
+                // Wait for a job, unless there are pages left to prefetch.
+                let job = if queue.is_empty() {
+                    match job_rx.recv() {
+                        Ok(job) => Some(job),
+                        Err(_) => break,
+                    }
+                } else {
+                    job_rx.try_recv().ok()
+                };
+
+                // Only the latest queued requests are of interest; skip stale ones.
+                let mut latest = None;
+                for job in job.into_iter().chain(job_rx.try_iter()) {
+                    match job {
+                        Job::Fetch { id, page_nr, shown } => latest = Some((id, page_nr, shown)),
+                        Job::Prefetch(pages) if !offline => queue = contiguous_ranges(pages),
+                        Job::Prefetch(_) => {}
+                    }
+                }
+
+                // Requested pages go before prefetching, which is done one
+                // range at a time to check for new requests in between.
+                if let Some((id, page_nr, shown)) = latest {
+                    let result = match shown {
+                        Some(shown) if !offline => {
+                            let (page, validators) = *shown;
+                            self::refresh(&source, cache.as_ref(), &page, &validators)
+                        }
+                        Some(_) => fetch(&source, cache.as_ref(), offline, page_nr),
+                        None => match prefetched.get(page_nr) {
+                            Some((response, validators)) => Ok(Fetch {
+                                response,
+                                validators,
+                                cached_unix: None,
+                                changed: true,
+                            }),
+                            None => fetch(&source, cache.as_ref(), offline, page_nr),
+                        },
+                    };
+                    let fetched = Fetched {
+                        id,
+                        page_nr,
+                        result,
+                    };
+                    if result_tx.send(fetched).is_err() {
+                        break;
+                    }
+                } else if let Some(range) = queue.pop() {
+                    let range = range
+                        .filter(|&page_nr| prefetched.get(page_nr).is_none())
+                        .collect::<Vec<_>>();
+                    for (response, validators) in prefetch(&source, cache.as_ref(), &range) {
+                        prefetched.insert(response, validators);
+                    }
+                }
+            }
+        });
+
+        Self {
+            jobs: job_tx,
+            results: result_rx,
+            next_id: 0,
+            source: PhantomData,
+        }
+    }
+
+    /// Request page `page_nr` from the worker. Returns an ID that identifies
+    /// the request in the [`Fetched`] result. Only prefetched pages are
+    /// returned without fetching them again; a requested page is fetched
+    /// each time.
+    pub fn request(&mut self, page_nr: u16) -> u64 {
+        self.send_fetch(page_nr, None)
+    }
+
+    /// Request the shown page `page` from the worker again, like
+    /// [`Worker::request`], but always from the source rather than from a
+    /// prefetched copy. The page is only fetched again if it has changed
+    /// since it was fetched with `validators`.
+    pub fn refresh(&mut self, page: PageResponse, validators: Validators) -> u64 {
+        self.send_fetch(page.num, Some(Box::new((page, validators))))
+    }
+
+    fn send_fetch(&mut self, page_nr: u16, shown: Option<Box<(PageResponse, Validators)>>) -> u64 {
+        self.next_id += 1;
+        // If the worker has stopped, the request is dropped; there is no result to wait for.
+        let _ = self.jobs.send(Job::Fetch {
+            id: self.next_id,
+            page_nr,
+            shown,
+        });
+        self.next_id
+    }
+
+    /// Ask the worker to fetch `pages` in the background, once there are no
+    /// requested pages to fetch, replacing any pages left from an earlier
+    /// call. A page requested within [`PREFETCH_TTL`] of being fetched is
+    /// returned at once. Nothing is prefetched if the worker is offline.
+    pub fn prefetch(&self, pages: Vec<u16>) {
+        let _ = self.jobs.send(Job::Prefetch(pages));
+    }
+
+    /// Returns the next available result, without blocking.
+    #[must_use]
+    pub fn try_recv(&self) -> Option<Fetched> {
+        self.results.try_recv().ok()
+    }
+}
+
+/// Completes a page `response` fetched from `source`, with the previous and
+/// next page from the source and decoded mosaics, and stores it in `cache`
+/// along with the `validators` of the response. A page fetched without
+/// validators of its own, e.g. as part of a range, keeps the validators
+/// stored with it.
+pub(crate) fn complete(
+    source: &impl PageSource,
+    cache: Option<&Cache>,
+    response: &mut PageResponse,
+    validators: Option<&Validators>,
+) {
+    response.prev_page = source.prev_page(response);
+    response.next_page = source.next_page(response);
+    resolve_mosaics(source, cache, response, false);
+    if let Some(cache) = cache {
+        let validators = match validators {
+            Some(validators) => validators.clone(),
+            None => cache
+                .load(response.num)
+                .ok()
+                .flatten()
+                .map(|cached| cached.validators)
+                .unwrap_or_default(),
+        };
+        // A failure to cache the page should not prevent reading it.
+        if let Err(err) = cache.store(response, &validators) {
+            log::warn!("could not cache page {}: {err}", response.num);
+        }
+    }
+}
+
+/// Fetches the pages in `range`, which are consecutive, with a single request
+/// if there is more than one. Pages that cannot be fetched are skipped, since
+/// they are fetched again when requested.
+fn prefetch(
+    source: &impl PageSource,
+    cache: Option<&Cache>,
+    range: &[u16],
+) -> Vec<(PageResponse, Validators)> {
+    let pages = match range {
+        [] => Ok(Vec::new()),
+        [page_nr] => source
+            .get_page_with_validators(*page_nr)
+            .map(|(page, validators)| vec![(page, Some(validators))]),
+        [lo, .., hi] => source
+            .get_page_range(*lo, *hi)
+            .map(|pages| pages.into_iter().map(|page| (page, None)).collect()),
+    };
+    let pages = pages.unwrap_or_else(|err| {
+        log::info!("could not prefetch pages {range:?}: {err}");
+        Vec::new()
+    });
+    pages
+        .into_iter()
+        .map(|(mut page, validators)| {
+            complete(source, cache, &mut page, validators.as_ref());
+            (page, validators.unwrap_or_default())
+        })
+        .collect()
+}
+
+/// Fetches page `page_nr` from `source`, or from the cache if `offline` is
+/// set or the request fails. Successfully fetched pages are cached.
+pub(crate) fn fetch(
+    source: &impl PageSource,
+    cache: Option<&Cache>,
+    offline: bool,
+    page_nr: u16,
+) -> Result<Fetch, Error> {
+    let err = if offline {
+        Error::NotCached(page_nr)
+    } else {
+        match source.get_page_with_validators(page_nr) {
+            Ok((mut response, validators)) => {
+                complete(source, cache, &mut response, Some(&validators));
+                return Ok(Fetch {
+                    response,
+                    validators,
+                    cached_unix: None,
+                    changed: true,
+                });
+            }
+            Err(err) => err,
+        }
+    };
+
+    match cache
+        .map(|cache| cache.load(page_nr))
+        .transpose()?
+        .flatten()
+    {
+        Some(cached) => {
+            resolve_mosaics(source, cache, &cached.page, offline);
+            Ok(Fetch {
+                response: cached.page,
+                validators: cached.validators,
+                cached_unix: Some(cached.cached_unix),
+                changed: true,
+            })
+        }
+        None => Err(err),
+    }
+}
+
+/// Fetches page `known.num` from `source` again, if it has changed since
+/// `known` was fetched with `validators`. An unchanged page is returned as
+/// `known`, without being stored again.
+pub(crate) fn refresh(
+    source: &impl PageSource,
+    cache: Option<&Cache>,
+    known: &PageResponse,
+    validators: &Validators,
+) -> Result<Fetch, Error> {
+    match source.get_page_if_changed(known, validators)? {
+        PageUpdate::Unchanged => Ok(Fetch {
+            response: known.clone(),
+            validators: validators.clone(),
+            cached_unix: None,
+            changed: false,
+        }),
+        PageUpdate::Changed {
+            page: mut response,
+            validators,
+        } => {
+            complete(source, cache, &mut response, Some(&validators));
+            Ok(Fetch {
+                response,
+                validators,
+                cached_unix: None,
+                changed: true,
+            })
+        }
+    }
+}
+
+/// Registers the mosaic GIFs decoded in earlier runs, stored in `cache`, with
+/// [`mosaic::register`].
+pub(crate) fn load_mosaics(cache: Option<&Cache>) {
+    let Some(cache) = cache else {
+        return;
+    };
+    match cache.load_mosaics() {
+        Ok(mosaics) => {
+            for (id, c) in mosaics {
+                mosaic::register(id, c);
+            }
+        }
+        Err(err) => log::warn!("could not load decoded mosaics: {err}"),
+    }
+}
+
+/// Decodes the mosaic GIFs in `response` that are missing from the lookup
+/// table, registers them with [`mosaic::register`], and stores them in
+/// `cache` for later runs. Unknown IDs are logged, so that they can be added
+/// to the table. If `offline` is set, or `source` has no images, the GIFs
+/// cannot be fetched and the mosaics are left blank, until they can be
+/// decoded.
+pub(crate) fn resolve_mosaics(
+    source: &impl PageSource,
+    cache: Option<&Cache>,
+    response: &PageResponse,
+    offline: bool,
+) {
+    let mut decoded_any = false;
+    for content in &response.content {
+        let Ok(images) = page::unknown_mosaics(content) else {
+            continue;
+        };
+        for image in images {
+            if offline {
+                log::warn!("unknown mosaic GIF {} on page {}", image.id, response.num);
+                continue;
+            }
+            let decoded = source
+                .get_image(&image.url)
+                .and_then(|bytes| mosaic::decode_gif(&bytes, image.fg.rgb(), image.bg.rgb()));
+            match decoded {
+                Ok(decoded) => {
+                    let c = decoded.glyph();
+                    log::warn!(
+                        "unknown mosaic GIF {} on page {}, decoded as '{c}'",
+                        image.id,
+                        response.num
+                    );
+                    mosaic::register(image.id, c);
+                    decoded_any = true;
+                }
+                Err(err) => log::warn!(
+                    "unknown mosaic GIF {} on page {}: {err}",
+                    image.id,
+                    response.num
+                ),
+            }
+        }
+    }
+    if let Some(cache) = cache.filter(|_| decoded_any) {
+        if let Err(err) = cache.store_mosaics(&mosaic::decoded()) {
+            log::warn!("could not cache decoded mosaics: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MemorySource;
+    use crate::texttv::test_page;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A source that counts the requests sent to it.
+    struct CountingSource {
+        inner: MemorySource,
+        requests: Arc<AtomicUsize>,
+    }
+
+    impl PageSource for CountingSource {
+        fn get_page(&self, number: u16) -> Result<PageResponse, Error> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            self.inner.get_page(number)
+        }
+
+        fn get_page_range(&self, lo: u16, hi: u16) -> Result<Vec<PageResponse>, Error> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            self.inner.get_page_range(lo, hi)
+        }
+    }
+
+    /// Waits for the result of request `id`.
+    fn wait_for<S: PageSource + Send + 'static>(worker: &Worker<S>, id: u64) -> Fetched {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            match worker.try_recv() {
+                Some(fetched) if fetched.id == id => return fetched,
+                _ => thread::sleep(Duration::from_millis(5)),
+            }
+        }
+        panic!("no result for request {id}");
+    }
+
+    #[test]
+    fn test_contiguous_ranges() {
+        assert_eq!(
+            contiguous_ranges(vec![378, 100, 376, 377, 101, 378]),
+            [100..=101, 376..=378]
+        );
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let source = CountingSource {
+            inner: [300, 376, 377, 378].into_iter().map(test_page).collect(),
+            requests: Arc::clone(&requests),
+        };
+        let mut worker = Worker::spawn(source, None, false);
+
+        let id = worker.request(377);
+        assert_eq!(wait_for(&worker, id).result.unwrap().response.num, 377);
+        // The pages are not contiguous, so each one takes a request.
+        worker.prefetch(vec![376, 378, 300, 378]);
+        let start = Instant::now();
+        while requests.load(Ordering::SeqCst) < 4 && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let id = worker.request(378);
+        assert_eq!(wait_for(&worker, id).result.unwrap().response.num, 378);
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+        let id = worker.refresh(test_page(378), Validators::default());
+        assert!(wait_for(&worker, id).result.is_ok());
+        assert_eq!(requests.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_request_again() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let source = CountingSource {
+            inner: [377].into_iter().map(test_page).collect(),
+            requests: Arc::clone(&requests),
+        };
+        let mut worker = Worker::spawn(source, None, false);
+
+        // A requested page is not kept as a prefetched copy, so requesting it
+        // again, e.g. to reload it, fetches it again.
+        for expected in 1..=2 {
+            let id = worker.request(377);
+            assert_eq!(wait_for(&worker, id).result.unwrap().response.num, 377);
+            assert_eq!(requests.load(Ordering::SeqCst), expected);
+        }
+    }
+
+    #[test]
+    fn test_complete_keeps_validators() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path());
+        let source = MemorySource::new();
+        let validators = Validators {
+            etag: Some("\"377\"".into()),
+            last_modified: None,
+        };
+        complete(
+            &source,
+            Some(&cache),
+            &mut test_page(377),
+            Some(&validators),
+        );
+
+        // A page from a range has no validators of its own.
+        complete(&source, Some(&cache), &mut test_page(377), None);
+        assert_eq!(cache.load(377).unwrap().unwrap().validators, validators);
+    }
+
+    #[test]
+    fn test_refresh_against_shown_page() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path());
+        let source = [377].into_iter().map(test_page).collect::<MemorySource>();
+        let fetched = fetch(&source, Some(&cache), false, 377).unwrap();
+        let result = refresh(
+            &source,
+            Some(&cache),
+            &fetched.response,
+            &fetched.validators,
+        );
+        assert!(!result.unwrap().changed);
+
+        // The cached page is up to date, but the shown page is not.
+        let mut shown = test_page(377);
+        shown.id = 1;
+        let result = refresh(&source, Some(&cache), &shown, &Validators::default());
+        assert_eq!(result.unwrap().response.id, 377);
+    }
+
+    #[test]
+    fn test_resolve_mosaics() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path());
+        let source = MemorySource::new();
+        let mut page = test_page(377);
+        page.content = vec![
+            concat!(
+                r#"<span class="line"><span class="bgB Y bgImg" "#,
+                r#"style="background-image: url('/images/43.gif')"> </span></span>"#
+            )
+            .into(),
+        ];
+
+        // Mosaics that cannot be decoded are left unknown, to be decoded later.
+        resolve_mosaics(&source, Some(&cache), &page, true);
+        resolve_mosaics(&source, Some(&cache), &page, false);
+        assert_eq!(mosaic::lookup(43), None);
+        assert!(cache.load_mosaics().unwrap().is_empty());
+
+        // Mosaics decoded in earlier runs are loaded from the cache.
+        cache.store_mosaics(&HashMap::from([(44, '█')])).unwrap();
+        load_mosaics(Some(&cache));
+        assert_eq!(mosaic::lookup(44), Some('█'));
+    }
+
+    #[test]
+    fn test_fetch_offline_without_cached_page() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path());
+        let source = MemorySource::new();
+
+        let result = fetch(&source, Some(&cache), true, 377);
+        assert!(matches!(result, Err(Error::NotCached(377))));
+    }
+}
@@ -0,0 +1,182 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use textty::Error;
+use textty::texttv::{Client, ClientBuilder, PageNumber, PageUpdate, Validators};
+
+const FIXTURE: &str = include_str!("fixtures/texttv/377.json");
+
+const ETAG: &str = "\"stub\"";
+const LAST_MODIFIED: &str = "Sat, 09 Mar 2024 17:42:00 GMT";
+
+/// Serve `responses` in order, one per connection, on a local port. Returns
+/// the base URL of the server and the request heads it receives.
+fn serve(responses: Vec<(u16, String)>) -> (String, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/api", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for (status, body) in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            while reader.read_line(&mut head).unwrap() > 2 {}
+            let _ = tx.send(head);
+            let mut stream = reader.into_inner();
+            write!(
+                stream,
+                "HTTP/1.1 {status} Stub\r\nContent-Type: application/json\r\n\
+                 ETag: {ETAG}\r\nLast-Modified: {LAST_MODIFIED}\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        }
+    });
+    (url, rx)
+}
+
+/// A client for `url` that retries without noticeable delays.
+fn client(url: String) -> ClientBuilder {
+    Client::builder()
+        .base_url(url)
+        .retry_delay(Duration::from_millis(10))
+        .min_interval(Duration::ZERO)
+}
+
+#[test]
+fn test_client_builder() {
+    let (url, requests) = serve(vec![(200, FIXTURE.to_string())]);
+    let client = Client::builder()
+        .base_url(url)
+        .app_id("textty-test")
+        .user_agent("textty-test/1.0")
+        .connect_timeout(Duration::from_secs(1))
+        .read_timeout(Duration::from_secs(1))
+        .build()
+        .unwrap();
+
+    let page = client.get_page(PageNumber::from(377)).unwrap();
+    assert_eq!(page.num, 377);
+    assert_eq!(page.title, "Fotboll");
+    assert_eq!((page.prev_page, page.next_page), (376, 378));
+
+    let head = requests.recv().unwrap();
+    let request_line = head.lines().next().unwrap();
+    assert!(
+        request_line.starts_with("GET /api/get/377?app=textty-test&includePlainTextContent=1 "),
+        "{request_line}"
+    );
+    assert!(
+        head.lines()
+            .any(|line| line.eq_ignore_ascii_case("user-agent: textty-test/1.0")),
+        "{head}"
+    );
+}
+
+#[test]
+fn test_client_builder_invalid_proxy() {
+    assert!(Client::builder().proxy("not a proxy").build().is_err());
+}
+
+#[test]
+fn test_retry_transient_errors() {
+    let (url, requests) = serve(vec![
+        (503, String::new()),
+        (500, String::new()),
+        (200, FIXTURE.to_string()),
+    ]);
+    let client = client(url).retries(2).build().unwrap();
+    assert_eq!(client.get_page(PageNumber::from(377)).unwrap().num, 377);
+    assert_eq!(requests.try_iter().count(), 3);
+}
+
+#[test]
+fn test_retry_gives_up() {
+    let (url, requests) = serve(vec![(503, String::new()), (503, String::new())]);
+    let client = client(url).retries(1).build().unwrap();
+    let result = client.get_page(PageNumber::from(377));
+    assert!(matches!(
+        result,
+        Err(Error::Transport(ureq::Error::StatusCode(503)))
+    ));
+    assert_eq!(requests.try_iter().count(), 2);
+}
+
+#[test]
+fn test_no_retry_on_client_error() {
+    let (url, requests) = serve(vec![(404, String::new())]);
+    let client = client(url).retries(3).build().unwrap();
+    let result = client.get_page(PageNumber::from(377));
+    assert!(matches!(
+        result,
+        Err(Error::Transport(ureq::Error::StatusCode(404)))
+    ));
+    assert_eq!(requests.try_iter().count(), 1);
+}
+
+#[test]
+fn test_rate_limit() {
+    let (url, _requests) = serve(vec![
+        (200, FIXTURE.to_string()),
+        (200, FIXTURE.to_string()),
+        (200, FIXTURE.to_string()),
+    ]);
+    let client = client(url)
+        .min_interval(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let start = Instant::now();
+    for _ in 0..3 {
+        client.get_page(PageNumber::from(377)).unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn test_get_page_if_changed() {
+    let (url, requests) = serve(vec![
+        (200, FIXTURE.to_string()),
+        (200, FIXTURE.to_string()),
+        (304, String::new()),
+        (200, FIXTURE.to_string()),
+    ]);
+    let client = client(url).build().unwrap();
+    // Every fetched page comes with its validators.
+    let (mut known, validators) = client
+        .get_page_with_validators(PageNumber::from(377))
+        .unwrap();
+    assert_eq!(validators.etag.as_deref(), Some(ETAG));
+    let _ = requests.recv().unwrap();
+
+    // A new page state is reported as changed, with the validators.
+    known.id = 1;
+    let PageUpdate::Changed { page, validators } = client
+        .get_page_if_changed(&known, &Validators::default())
+        .unwrap()
+    else {
+        panic!("page 377 should have changed");
+    };
+    assert_eq!(page.id, 1234);
+    assert_eq!(validators.etag.as_deref(), Some(ETAG));
+    assert_eq!(validators.last_modified.as_deref(), Some(LAST_MODIFIED));
+    let _ = requests.recv().unwrap();
+
+    // The request is conditional on the validators.
+    let update = client.get_page_if_changed(&page, &validators).unwrap();
+    assert!(matches!(update, PageUpdate::Unchanged));
+    let head = requests.recv().unwrap().to_ascii_lowercase();
+    assert!(head.contains("if-none-match: \"stub\""), "{head}");
+    assert!(
+        head.contains("if-modified-since: sat, 09 mar 2024 17:42:00 gmt"),
+        "{head}"
+    );
+
+    // Without validators, the page is compared by its state.
+    let update = client
+        .get_page_if_changed(&page, &Validators::default())
+        .unwrap();
+    assert!(matches!(update, PageUpdate::Unchanged));
+}